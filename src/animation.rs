@@ -0,0 +1,303 @@
+// Animated GIF and APNG export of grid simulations
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+/// A palette colour, as red, green and blue.
+pub type Color = [u8; 3];
+
+/// One picture of a grid simulation, holding a palette index per cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl Frame {
+    /// A frame painted with the first palette colour.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![0; width * height],
+        }
+    }
+
+    /// A frame of a character grid, coloured tile by tile. Rows may differ in length, the
+    /// frame is as wide as the longest one and the missing cells keep the first colour.
+    pub fn from_tiles(rows: &[Vec<char>], color: impl Fn(char) -> u8) -> Self {
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut frame = Self::new(width, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                frame.cells[y * width + x] = color(tile);
+            }
+        }
+        frame
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The colour of a cell, or `None` outside the frame.
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.cells[y * self.width + x])
+    }
+
+    /// Paints a cell, failing for cells outside the frame.
+    pub fn set(&mut self, x: usize, y: usize, color: u8) -> Result<()> {
+        if x >= self.width || y >= self.height {
+            bail!(
+                "Cell ({}, {}) is outside the {}x{} frame",
+                x,
+                y,
+                self.width,
+                self.height
+            );
+        }
+        self.cells[y * self.width + x] = color;
+        Ok(())
+    }
+}
+
+/// Reads the `--every` option, the number of simulation steps per frame, which must be at
+/// least 1.
+pub fn every_option(default: usize) -> Result<usize> {
+    parse_every(crate::cli_option("every").as_deref(), default)
+}
+
+fn parse_every(value: Option<&str>, default: usize) -> Result<usize> {
+    match value {
+        Some(value) => Ok(value
+            .parse::<NonZeroUsize>()
+            .with_context(|| format!("--every needs a positive number of steps, not '{}'", value))?
+            .get()),
+        None => Ok(default.max(1)),
+    }
+}
+
+/// Frames of the same size sharing a palette, played in a loop.
+pub struct Animation {
+    palette: Vec<Color>,
+    delay_ms: u16,
+    scale: usize,
+    frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub fn new(palette: Vec<Color>, delay_ms: u16) -> Self {
+        Self {
+            palette,
+            delay_ms,
+            scale: 1,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Draws every cell as a `scale` x `scale` square of pixels.
+    pub fn set_scale(&mut self, scale: usize) {
+        self.scale = scale.max(1);
+    }
+
+    pub fn push(&mut self, frame: Frame) -> Result<()> {
+        if let Some(first) = self.frames.first() {
+            if (first.width, first.height) != (frame.width, frame.height) {
+                bail!(
+                    "Frame is {}x{} while the animation is {}x{}",
+                    frame.width,
+                    frame.height,
+                    first.width,
+                    first.height
+                );
+            }
+        }
+        if let Some(&color) = frame
+            .cells
+            .iter()
+            .find(|&&c| c as usize >= self.palette.len())
+        {
+            bail!(
+                "Colour {} is not in the palette of {} colours",
+                color,
+                self.palette.len()
+            );
+        }
+
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Writes a GIF for a `.gif` path and an APNG for a `.png` or `.apng` one.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        let writer = BufWriter::new(File::create(path)?);
+        match extension.as_deref() {
+            Some("gif") => self.write_gif(writer),
+            Some("png") | Some("apng") => self.write_apng(writer),
+            _ => Err(anyhow!(
+                "Cannot tell the format of {}, use .gif, .png or .apng",
+                path.display()
+            )),
+        }
+    }
+
+    pub fn write_gif<W: Write>(&self, writer: W) -> Result<()> {
+        let (width, height) = self.size()?;
+
+        // GIF colour tables hold a power of two entries
+        let mut palette = self.palette.concat();
+        palette.resize(3 * self.palette.len().next_power_of_two().max(2), 0);
+
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &palette)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for frame in self.frames.iter() {
+            let mut gif_frame = gif::Frame::from_indexed_pixels(
+                width as u16,
+                height as u16,
+                self.pixels(frame),
+                None,
+            );
+            gif_frame.delay = self.delay_ms / 10;
+            encoder.write_frame(&gif_frame)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_apng<W: Write>(&self, writer: W) -> Result<()> {
+        let (width, height) = self.size()?;
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(self.palette.concat());
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+        encoder.set_frame_delay(self.delay_ms, 1000)?;
+
+        let mut writer = encoder.write_header()?;
+        for frame in self.frames.iter() {
+            writer.write_image_data(&self.pixels(frame))?;
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Size in pixels, checked against the limits of the formats.
+    fn size(&self) -> Result<(usize, usize)> {
+        let first = self
+            .frames
+            .first()
+            .ok_or_else(|| anyhow!("The animation has no frames"))?;
+        if self.palette.is_empty() || self.palette.len() > 256 {
+            bail!(
+                "The palette needs 1 to 256 colours, not {}",
+                self.palette.len()
+            );
+        }
+
+        let (width, height) = (first.width * self.scale, first.height * self.scale);
+        if width == 0 || height == 0 || width > u16::MAX as usize || height > u16::MAX as usize {
+            bail!("Cannot encode {}x{} pixel frames", width, height);
+        }
+
+        Ok((width, height))
+    }
+
+    fn pixels(&self, frame: &Frame) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(frame.cells.len() * self.scale * self.scale);
+        for row in frame.cells.chunks(frame.width) {
+            let line = row
+                .iter()
+                .flat_map(|&c| std::iter::repeat_n(c, self.scale))
+                .collect::<Vec<u8>>();
+            for _ in 0..self.scale {
+                pixels.extend_from_slice(&line);
+            }
+        }
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_both_formats() {
+        let mut animation = Animation::new(vec![[0, 0, 0], [255, 255, 255], [255, 0, 0]], 100);
+        animation.set_scale(2);
+        for i in 0..3 {
+            let mut frame = Frame::new(3, 2);
+            frame.set(i, 1, 2).unwrap();
+            animation.push(frame).unwrap();
+        }
+        assert!(animation.push(Frame::new(2, 2)).is_err());
+
+        let mut gif = Vec::new();
+        animation.write_gif(&mut gif).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+
+        let mut apng = Vec::new();
+        animation.write_apng(&mut apng).unwrap();
+        assert!(apng.windows(4).any(|w| w == b"acTL"));
+    }
+
+    #[test]
+    fn ragged_tiles() {
+        let rows = vec!["#.".chars().collect(), "#.@#".chars().collect()];
+        let frame = Frame::from_tiles(&rows, |tile| match tile {
+            '#' => 1,
+            '@' => 2,
+            _ => 0,
+        });
+        assert_eq!((4, 2), (frame.width(), frame.height()));
+        assert_eq!(Some(0), frame.get(3, 0));
+        assert_eq!(Some(2), frame.get(2, 1));
+        assert_eq!(Some(1), frame.get(3, 1));
+    }
+
+    #[test]
+    fn cells_outside_the_frame() {
+        let mut frame = Frame::new(3, 2);
+        // Past the end of a row must not wrap into the next one
+        assert!(frame.set(3, 0, 1).is_err());
+        assert!(frame.set(0, 2, 1).is_err());
+        assert_eq!(Some(0), frame.get(0, 1));
+        assert_eq!(None, frame.get(3, 0));
+    }
+
+    #[test]
+    fn every_is_positive() {
+        assert_eq!(10, parse_every(None, 10).unwrap());
+        assert_eq!(1, parse_every(None, 0).unwrap());
+        assert_eq!(3, parse_every(Some("3"), 10).unwrap());
+        assert!(parse_every(Some("0"), 10).is_err());
+        assert!(parse_every(Some("x"), 10).is_err());
+    }
+}
@@ -1,11 +1,16 @@
+use adv_code_2024::*;
 use anyhow::*;
-use std::fs::File;
-use std::io::{Read};
 use code_timing_macros::time_snippet;
 use const_format::concatcp;
-use adv_code_2024::*;
+use itertools::Itertools;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
 
-const DAY: &str = "01"; // TODO: Fill the day
+const DAY: &str = "01";
 const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
 
 const TEST: &str = "\
@@ -17,86 +22,227 @@ const TEST: &str = "\
 3   3
 ";
 
+// How many of the most frequent IDs the summary lists
+const MOST_FREQUENT: usize = 3;
+
 fn main() -> Result<()> {
     start_day(DAY);
 
     println!("=== Part 1 ===");
 
-    fn part1(input: &str) -> Result<u64> {
-        let parsing = parse(input)?;
-        let test_result = distance(&parsing.0, &parsing.1)?;
-        Ok(test_result)
+    fn part1(input: impl BufRead) -> Result<u64> {
+        let lists = LocationLists::parse(input)?;
+        Ok(lists.distance())
     }
 
-    fn part2(input: &str) -> Result<u64> {
-        let parsing = parse(input)?;
-        let test_result = similarity(&parsing.0, &parsing.1)?;
-        Ok(test_result)
+    fn part2(input: impl BufRead) -> Result<u64> {
+        let lists = LocationLists::parse(input)?;
+        Ok(lists.similarity())
     }
 
-    // TODO: Set the expected answer for the test input
-    let result = part1(&TEST)?;
+    let result = part1(TEST.as_bytes())?;
     println!("Test Result 1 = {}", result);
     assert_eq!(11, result);
 
-    // TODO: Set the expected answer for the test input
-    let result = part2(&TEST)?;
+    let result = part2(TEST.as_bytes())?;
     println!("Test Result 2 = {}", result);
     assert_eq!(31, result);
 
-    let mut input_file = File::open(INPUT_FILE)?;
-    let mut buffer = Vec::new();
-    input_file.read_to_end(&mut buffer)?;
-    let input = String::from_utf8_lossy(&buffer);
-
-    let result = time_snippet!(part1(&input)?);
+    // No phantom IDs, and rows must all have both columns
+    let lists = LocationLists::parse(TEST.as_bytes())?;
+    assert_eq!(vec![1, 2, 3, 3, 3, 4], lists.left);
+    let error = parse_columns::<u64>("1 2\n3\n".as_bytes(), 2).unwrap_err();
+    println!("{}", error);
+    assert_eq!("Line 2 has 1 columns instead of 2", error.to_string());
+    assert!(parse_columns::<u64>("1 x\n".as_bytes(), 2).is_err());
+
+    let summary = lists.summary();
+    print!("{}", summary);
+    assert_eq!(
+        (1, 4, 3.0),
+        (summary.left.min, summary.left.max, summary.left.median)
+    );
+    assert_eq!(
+        (3, 9, 3.5),
+        (summary.right.min, summary.right.max, summary.right.median)
+    );
+    assert_eq!(vec![(3, 3), (1, 1), (2, 1)], summary.left.most_frequent);
+    assert_eq!(vec![1, 2], summary.only_left);
+    assert_eq!(vec![5, 9], summary.only_right);
+
+    let result = time_snippet!(part1(BufReader::new(File::open(INPUT_FILE)?))?);
     println!("Result 1 = {}", result);
 
-    let result = time_snippet!(part2(&input)?);
+    let result = time_snippet!(part2(BufReader::new(File::open(INPUT_FILE)?))?);
     println!("Result 2 = {}", result);
 
+    if cli_flag("summary") {
+        let lists = LocationLists::parse(BufReader::new(File::open(INPUT_FILE)?))?;
+        print!("{}", lists.summary());
+    }
+
     Ok(())
 }
 
-fn parse(input: &str) -> Result<(Vec<u64>, Vec<u64>)> {
-    let mut first = vec![0, 0];
-    let mut second = vec![0, 0];
-
-    for line in input.lines() {
-        if line.is_empty() {
+/// Reads whitespace separated columns line by line, failing on rows with another number of
+/// columns or on values that do not parse.
+fn parse_columns<T>(input: impl BufRead, count: usize) -> Result<Vec<Vec<T>>>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let mut columns: Vec<Vec<T>> = (0..count).map(|_| Vec::new()).collect();
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
             continue;
         }
 
-        let items: Vec<u64> = line.split(' ')
-            .filter(|s| !s.is_empty())
-            .map(|x| x.parse::<u64>().unwrap()).collect();
-        first.push(items[0].clone());
-        second.push(items[1].clone());
+        let values = line.split_whitespace().collect_vec();
+        if values.len() != count {
+            bail!(
+                "Line {} has {} columns instead of {}",
+                number + 1,
+                values.len(),
+                count
+            );
+        }
+        for (column, value) in columns.iter_mut().zip(values) {
+            let value = value
+                .parse()
+                .with_context(|| format!("Line {}: cannot read '{}'", number + 1, value))?;
+            column.push(value);
+        }
     }
 
-    first.sort();
-    second.sort();
+    Ok(columns)
+}
 
-    Ok((first, second))
+/// The two historians' lists of location IDs, each sorted.
+#[derive(Debug, Clone)]
+struct LocationLists {
+    left: Vec<u64>,
+    right: Vec<u64>,
 }
 
-fn distance(first: &Vec<u64>, second: &Vec<u64>) -> Result<u64> {
+impl LocationLists {
+    fn parse(input: impl BufRead) -> Result<Self> {
+        let mut columns = parse_columns::<u64>(input, 2)?;
+        let mut right = columns.pop().unwrap();
+        let mut left = columns.pop().unwrap();
+        left.sort_unstable();
+        right.sort_unstable();
+        Ok(LocationLists { left, right })
+    }
+
+    /// Pairs the smallest IDs together, then the next ones, and so on.
+    fn distance(&self) -> u64 {
+        self.left
+            .iter()
+            .zip(self.right.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .sum()
+    }
 
-    let mut total = 0;
-    for i in 0..first.len() {
-        total = total + first[i].abs_diff(second[i]);
+    /// Each left ID times the number of times it appears on the right.
+    fn similarity(&self) -> u64 {
+        let counts = counts(&self.right);
+        self.left
+            .iter()
+            .map(|id| id * *counts.get(id).unwrap_or(&0) as u64)
+            .sum()
     }
 
-    Ok(total)
+    fn summary(&self) -> Summary {
+        let left: BTreeSet<u64> = self.left.iter().copied().collect();
+        let right: BTreeSet<u64> = self.right.iter().copied().collect();
+
+        Summary {
+            left: Side::new(&self.left),
+            right: Side::new(&self.right),
+            only_left: left.difference(&right).copied().collect(),
+            only_right: right.difference(&left).copied().collect(),
+        }
+    }
 }
 
-fn similarity(first: &Vec<u64>, second: &Vec<u64>) -> Result<u64> {
+fn counts(ids: &[u64]) -> HashMap<u64, usize> {
+    let mut counts = HashMap::new();
+    for &id in ids {
+        *counts.entry(id).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Statistics of one sorted list.
+#[derive(Debug, Clone)]
+struct Side {
+    min: u64,
+    max: u64,
+    median: f64,
+    /// IDs with how often they appear, most frequent first then by ID.
+    most_frequent: Vec<(u64, usize)>,
+}
 
-    let mut total = 0;
-    for i in 0..first.len() {
-        let count = second.iter().filter(|&n| *n == first[i]).count();
-        total = total + first[i]*count as u64;
+impl Side {
+    fn new(ids: &[u64]) -> Self {
+        let n = ids.len();
+        let median = match n {
+            0 => 0.0,
+            _ if n % 2 == 1 => ids[n / 2] as f64,
+            _ => (ids[n / 2 - 1] as f64 + ids[n / 2] as f64) / 2.0,
+        };
+        let most_frequent = counts(ids)
+            .into_iter()
+            .sorted_by_key(|&(id, count)| (std::cmp::Reverse(count), id))
+            .take(MOST_FREQUENT)
+            .collect();
+
+        Side {
+            min: ids.first().copied().unwrap_or(0),
+            max: ids.last().copied().unwrap_or(0),
+            median,
+            most_frequent,
+        }
     }
+}
+
+#[derive(Debug, Clone)]
+struct Summary {
+    left: Side,
+    right: Side,
+    /// IDs found on one side only.
+    only_left: Vec<u64>,
+    only_right: Vec<u64>,
+}
 
-    Ok(total)
-}
\ No newline at end of file
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (name, side, missing) in [
+            ("Left", &self.left, &self.only_left),
+            ("Right", &self.right, &self.only_right),
+        ] {
+            writeln!(
+                f,
+                "{:<5} min {} max {} median {}",
+                name, side.min, side.max, side.median
+            )?;
+            writeln!(
+                f,
+                "      most frequent {}",
+                side.most_frequent
+                    .iter()
+                    .map(|(id, count)| format!("{} x{}", id, count))
+                    .join(", ")
+            )?;
+            writeln!(
+                f,
+                "      {} IDs missing from the other side: {}",
+                missing.len(),
+                missing.iter().join(" ")
+            )?;
+        }
+        fmt::Result::Ok(())
+    }
+}
@@ -2,7 +2,9 @@ use adv_code_2024::*;
 use anyhow::*;
 use code_timing_macros::time_snippet;
 use const_format::concatcp;
-use itertools::{all, Itertools};
+use itertools::Itertools;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 
@@ -32,114 +34,321 @@ fn main() -> Result<()> {
     println!("=== Part 1 ===");
 
     fn part1(input: &str) -> Result<usize> {
-        let parsing = parse(input)?;
-        let total_safe = parsing
+        let analyser = Analyser::default();
+        let reports = parse(input)?;
+        Ok(reports
             .iter()
-            .filter(|&r| check(r))
-            .count();
-        Ok(total_safe)
+            .filter(|r| analyser.analyse(r).is_safe())
+            .count())
     }
 
     fn part2(input: &str) -> Result<usize> {
-        let parsing = parse(input)?;
-        let total_safe = parsing
+        let analyser = Analyser {
+            removals: 1,
+            ..Analyser::default()
+        };
+        let reports = parse(input)?;
+        Ok(reports
             .iter()
-            .filter(|&r| is_safe_full(r).unwrap().0)
-            .count();
-        Ok(total_safe)
+            .filter(|r| analyser.analyse(r).is_safe())
+            .count())
     }
 
-    let result = part1(&TEST)?;
+    let result = part1(TEST)?;
     println!("Test Result 1 = {}", result);
     assert_eq!(2, result);
 
-    let result = part2(&TEST)?;
+    let result = part2(TEST)?;
     println!("Test Result 2 = {}", result);
     assert_eq!(9, result);
 
+    // Which levels go, and why the others stay unsafe
+    let reports = parse(TEST)?;
+    let analyser = Analyser {
+        removals: 1,
+        ..Analyser::default()
+    };
+    for report in reports.iter() {
+        println!("{}", Analysis::new(&analyser, report));
+    }
+    assert_eq!(Verdict::Dampened(vec![7]), analyser.analyse(&reports[0]));
+    assert_eq!(Verdict::Dampened(vec![0]), analyser.analyse(&reports[1]));
+    assert_eq!(Verdict::Safe, analyser.analyse(&reports[2]));
+    assert_eq!(Verdict::Dampened(vec![1]), analyser.analyse(&reports[5]));
+    assert_eq!(
+        "1 2 7 8 9: unsafe, levels 2 and 3 (2 -> 7) differ by 5, more than 3",
+        Analysis::new(&analyser, &reports[3]).to_string()
+    );
+    assert_eq!(
+        "1 3 2 4 5: safe without level 2 (3)",
+        Analysis::new(&analyser, &reports[5]).to_string()
+    );
+
+    // Other tolerances
+    let count = |analyser: Analyser| {
+        reports
+            .iter()
+            .filter(|r| analyser.analyse(r).is_safe())
+            .count()
+    };
+    let two_removals = Analyser {
+        removals: 2,
+        ..Analyser::default()
+    };
+    assert_eq!(12, count(two_removals));
+    assert_eq!(
+        Verdict::Dampened(vec![0, 1]),
+        two_removals.analyse(&reports[3])
+    );
+    let flat = Analyser {
+        min_step: 0,
+        ..Analyser::default()
+    };
+    assert_eq!(7, count(flat));
+    assert_eq!(
+        10,
+        count(Analyser {
+            removals: 1,
+            ..flat
+        })
+    );
+    let wide = Analyser {
+        max_step: 5,
+        ..Analyser::default()
+    };
+    assert_eq!(5, count(wide));
+
     let mut input_file = File::open(INPUT_FILE)?;
     let mut buffer = Vec::new();
     input_file.read_to_end(&mut buffer)?;
     let input = String::from_utf8_lossy(&buffer);
-    
+
     let result = time_snippet!(part1(&input)?);
     println!("Result 1 = {}", result);
-    
+
     let result = time_snippet!(part2(&input)?);
     println!("Result 2 = {}", result);
 
+    if cli_flag("explain") {
+        let mut analyser = Analyser::default();
+        if let Some(min_step) = cli_option("min-step") {
+            analyser.min_step = min_step.parse()?;
+        }
+        if let Some(max_step) = cli_option("max-step") {
+            analyser.max_step = max_step.parse()?;
+        }
+        analyser.removals = match cli_option("removals") {
+            Some(removals) => removals.parse()?,
+            None => 1,
+        };
+
+        for report in parse(&input)?.iter() {
+            println!("{}", Analysis::new(&analyser, report));
+        }
+    }
+
     Ok(())
 }
 
 fn parse(input: &str) -> Result<Vec<Vec<i64>>> {
     let mut reports: Vec<Vec<i64>> = Vec::new();
 
-    for line in input.lines() {
-        if line.is_empty() {
+    for (number, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
             continue;
         }
 
-        let levels: Vec<i64> = line
-            .split(' ')
-            .filter(|s| !s.is_empty())
-            .map(|x| x.parse::<i64>().unwrap())
-            .collect();
+        let levels = line
+            .split_whitespace()
+            .map(|x| x.parse::<i64>())
+            .collect::<std::result::Result<Vec<i64>, _>>()
+            .map_err(|e| anyhow!("Report on line {}: {}", number + 1, e))?;
         reports.push(levels);
     }
 
     Ok(reports)
 }
 
-fn is_safe_full(report: &Vec<i64>) -> Result<(bool, &Vec<i64>)> {
+/// Why a report is unsafe: the first pair of neighbouring levels that breaks the rules,
+/// by the index of the first level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    Step(usize),
+    Direction(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Verdict {
+    Safe,
+    /// Safe once the levels at these indices are dropped.
+    Dampened(Vec<usize>),
+    Unsafe(Problem),
+}
 
-    let report_result = check(&report);
-    if report_result {
-        return Ok((true, report));
+impl Verdict {
+    fn is_safe(&self) -> bool {
+        !matches!(self, Verdict::Unsafe(_))
     }
+}
 
-    for index_external in 0..report.len() {
-        let mut new_report = report.clone();
-        new_report.remove(index_external);
+/// How far apart neighbouring levels may be, all going the same way, and how many levels
+/// the Problem Dampener may drop.
+#[derive(Debug, Copy, Clone)]
+struct Analyser {
+    min_step: i64,
+    max_step: i64,
+    removals: usize,
+}
 
-        let report_result = check(&new_report);
-        if report_result {
-            return Ok((true, report));
+impl Default for Analyser {
+    fn default() -> Self {
+        Analyser {
+            min_step: 1,
+            max_step: 3,
+            removals: 0,
         }
     }
-
-    //println!("No valid: {}", report.iter().map(|f| f.to_string()).collect::<Vec<_>>().iter().join(" "));
-    Ok((false, report))
 }
 
-fn check(new_report: &Vec<i64>) -> bool {
-    let mut direction = 0isize;
-    for i in 0..new_report.len() - 1 {
-        let compare_result = compare(new_report[i], new_report[i + 1], &mut direction).unwrap();
-        if !compare_result {
-            return false;
-        }
+impl Analyser {
+    fn allows(&self, from: i64, to: i64, direction: i64) -> bool {
+        (self.min_step..=self.max_step).contains(&((to - from) * direction))
     }
 
-    true
-}
+    fn analyse(&self, report: &[i64]) -> Verdict {
+        // Going up, then going down
+        let mut best: Option<Vec<usize>> = None;
+        for direction in [1, -1] {
+            if let Some(dropped) = self.fewest_drops(report, direction) {
+                if best.as_ref().is_none_or(|best| dropped.len() < best.len()) {
+                    best = Some(dropped);
+                }
+            }
+        }
 
-fn compare(first: i64, second: i64, direction: &mut isize) -> Result<bool> {
-    let diff = first.abs_diff(second);
-    if diff < 1 || diff > 3 {
-        return Ok(false);
+        match best {
+            Some(dropped) if dropped.is_empty() => Verdict::Safe,
+            Some(dropped) => Verdict::Dampened(dropped),
+            None => Verdict::Unsafe(self.problem(report)),
+        }
     }
 
-    if first > second {
-        if *direction == 0isize {
-            *direction = 1;
+    /// The fewest levels to drop, if no more than `removals`, so the rest goes the given way.
+    /// Two levels kept next to each other are at most `removals + 1` apart, so the search
+    /// is O(n * removals): linear for the Problem Dampener.
+    fn fewest_drops(&self, report: &[i64], direction: i64) -> Option<Vec<usize>> {
+        let n = report.len();
+        if n == 0 {
+            return Some(Vec::new());
+        }
+
+        // Drops needed with level i kept last, and the level kept before it. On ties the
+        // closest one wins, so the level dropped is the one breaking the rules, if any.
+        let mut drops: Vec<Option<usize>> = vec![None; n];
+        let mut previous: Vec<Option<usize>> = vec![None; n];
+        for i in 0..n {
+            if i <= self.removals {
+                drops[i] = Some(i);
+            }
+            for p in i.saturating_sub(self.removals + 1)..i {
+                let Some(before) = drops[p] else { continue };
+                let total = before + i - p - 1;
+                if self.allows(report[p], report[i], direction)
+                    && total <= self.removals
+                    && drops[i].is_none_or(|d| total <= d)
+                {
+                    drops[i] = Some(total);
+                    previous[i] = Some(p);
+                }
+            }
         }
 
-        return Ok(*direction == 1isize);
+        let last = ((n - 1).saturating_sub(self.removals)..n)
+            .filter(|&i| drops[i].is_some_and(|d| d + n - 1 - i <= self.removals))
+            .min_by_key(|&i| drops[i].unwrap() + n - 1 - i)?;
+
+        let mut kept = vec![last];
+        while let Some(p) = previous[*kept.last().unwrap()] {
+            kept.push(p);
+        }
+        Some((0..n).filter(|i| !kept.contains(i)).collect())
+    }
+
+    /// The first bad pair, read the way the first two levels go.
+    fn problem(&self, report: &[i64]) -> Problem {
+        let direction = if report[1] >= report[0] { 1 } else { -1 };
+        let at = (0..report.len() - 1)
+            .find(|&i| !self.allows(report[i], report[i + 1], direction))
+            .unwrap();
+        if (report[at + 1] - report[at]) * direction < 0 {
+            Problem::Direction(at)
+        } else {
+            Problem::Step(at)
+        }
     }
+}
 
-    if *direction == 0isize {
-        *direction = -1;
+/// A report with its verdict, written for people.
+struct Analysis<'a> {
+    analyser: &'a Analyser,
+    report: &'a [i64],
+    verdict: Verdict,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(analyser: &'a Analyser, report: &'a [i64]) -> Self {
+        Analysis {
+            analyser,
+            report,
+            verdict: analyser.analyse(report),
+        }
     }
+}
+
+impl Display for Analysis<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.report.iter().join(" "))?;
+        let (at, from, to) = match &self.verdict {
+            Verdict::Safe => return write!(f, "safe"),
+            Verdict::Dampened(dropped) => {
+                let levels = dropped
+                    .iter()
+                    .map(|&i| format!("{} ({})", i + 1, self.report[i]))
+                    .join(", ");
+                let plural = if dropped.len() == 1 { "" } else { "s" };
+                return write!(f, "safe without level{} {}", plural, levels);
+            }
+            Verdict::Unsafe(Problem::Step(at)) | Verdict::Unsafe(Problem::Direction(at)) => {
+                (*at, self.report[*at], self.report[*at + 1])
+            }
+        };
 
-    Ok(*direction == -1isize)
+        write!(
+            f,
+            "unsafe, levels {} and {} ({} -> {}) ",
+            at + 1,
+            at + 2,
+            from,
+            to
+        )?;
+        let step = from.abs_diff(to) as i64;
+        match self.verdict {
+            Verdict::Unsafe(Problem::Direction(_)) => write!(
+                f,
+                "go {} while the report goes {}",
+                if to > from { "up" } else { "down" },
+                if to > from { "down" } else { "up" }
+            ),
+            _ if step < self.analyser.min_step => write!(
+                f,
+                "differ by {}, less than {}",
+                step, self.analyser.min_step
+            ),
+            _ => write!(
+                f,
+                "differ by {}, more than {}",
+                step, self.analyser.max_step
+            ),
+        }
+    }
 }
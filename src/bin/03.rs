@@ -2,13 +2,17 @@ use adv_code_2024::*;
 use anyhow::*;
 use code_timing_macros::time_snippet;
 use const_format::concatcp;
-use itertools::{all, Itertools};
+use itertools::Itertools;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::Read;
-use regex::Regex;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
 
 const DAY: &str = "03";
 const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
+// Digits an argument may have and still fit an i64
+const MAX_DIGITS: usize = 18;
 const TEST: &str = "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
 const TEST2: &str = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))^don't()_mul(5,5)+mul(32,64]";
 
@@ -18,62 +22,518 @@ fn main() -> Result<()> {
     //region Part 1
     println!("=== Part 1 ===");
 
-    fn part1(input: &str) -> Result<i64> {
-        let parsing = parse(input)?;
-        Ok(parsing)
+    fn part1(input: impl BufRead) -> Result<i64> {
+        let set = InstructionSet::new().with(Mul);
+        set.run_reader(input, |_| {})
     }
 
-    fn part2(input: &str) -> Result<i64> {
-        let parsing = parse_with_do_and_dont(input)?;
-        Ok(parsing)
+    fn part2(input: impl BufRead) -> Result<i64> {
+        let set = InstructionSet::new().with(Mul).with(Do).with(Dont);
+        set.run_reader(input, |_| {})
     }
 
-    let result = part1(&TEST)?;
+    let result = part1(TEST.as_bytes())?;
     println!("Test Result 1 = {}", result);
     assert_eq!(161, result);
 
-    let result = part2(&TEST2)?;
+    let result = part2(TEST2.as_bytes())?;
     println!("Test Result 2 = {}", result);
     assert_eq!(48, result);
 
-    let mut input_file = File::open(INPUT_FILE)?;
-    let mut buffer = Vec::new();
-    input_file.read_to_end(&mut buffer)?;
-    let input = String::from_utf8_lossy(&buffer);
+    // Tokens cover the whole input, instructions and noise alike
+    let set = InstructionSet::new().with(Mul).with(Do).with(Dont);
+    let tokens = set.tokenize(TEST2).collect_vec();
+    assert_eq!(1..9, tokens[1].span);
+    assert_eq!(
+        TEST2.len(),
+        tokens.iter().map(|t| t.span.len()).sum::<usize>()
+    );
+    let run = set.run(TEST2)?;
+    for entry in run.entries.iter() {
+        println!("{}", entry);
+    }
+    assert_eq!(5, run.entries.iter().filter(|e| e.accepted).count());
+    assert_eq!(3, run.entries.iter().filter(|e| !e.accepted).count());
+
+    // Instructions cut between chunks are put back together
+    assert_eq!(161, part1(BufReader::with_capacity(1, TEST.as_bytes()))?);
+    assert_eq!(48, part2(BufReader::with_capacity(5, TEST2.as_bytes()))?);
+    let reader = BufReader::with_capacity(1, "mul(123,456)mul(1234,5)".as_bytes());
+    assert_eq!(56088, part1(reader)?);
+
+    // A chunk ending right on the last allowed digit may still hold a whole argument,
+    // while the input ending there cannot
+    let reader = BufReader::with_capacity(9, "mul(2,345)".as_bytes());
+    assert_eq!(690, part1(reader)?);
+    assert_eq!(0, part1("mul(2,345".as_bytes())?);
+    let tokens = set.tokenize("mul(2,345").collect_vec();
+    assert_eq!(
+        vec![TokenKind::Noise],
+        tokens.iter().map(|t| t.kind.clone()).collect_vec()
+    );
+
+    // Every copy after the first starts disabled, so only mul(8,5) counts in them
+    let repeat = Repeat {
+        bytes: TEST2.as_bytes(),
+        position: 0,
+        remaining: 1000,
+    };
+    assert_eq!(48 + 40 * 999, part2(BufReader::with_capacity(7, repeat))?);
 
-    let result = time_snippet!(part1(&input)?);
+    // More instructions and longer numbers
+    let set = InstructionSet::new().with(Mul).with(Add).with(Sub);
+    assert_eq!(14, set.run("add(1,2)sub(10,3)mul(2,2)add(1)")?.total);
+    assert_eq!(0, set.run("mul(1000,2)")?.total);
+    let set = set.with_digits(1..=4);
+    assert_eq!(2000, set.run("mul(1000,2)")?.total);
+
+    // Results that do not fit are errors, and arguments always fit
+    let set = InstructionSet::new()
+        .with(Mul)
+        .with(Add)
+        .with_digits(1..=30);
+    assert!(set.run("mul(9999999999,9999999999)").is_err());
+    let big = "add(999999999999999999,999999999999999999)".repeat(5);
+    assert!(set.run(&big).is_err());
+    assert_eq!(0, set.run("mul(1234567890123456789012,1)")?.total);
+
+    let result = time_snippet!(part1(BufReader::new(File::open(INPUT_FILE)?))?);
     println!("Result 1 = {}", result);
 
-    let result = time_snippet!(part2(&input)?);
+    let result = time_snippet!(part2(BufReader::new(File::open(INPUT_FILE)?))?);
     println!("Result 2 = {}", result);
 
+    if let Some(megabytes) = cli_option("stress") {
+        let copies = megabytes.parse::<usize>()? * 1_000_000 / TEST2.len();
+        let repeat = Repeat {
+            bytes: TEST2.as_bytes(),
+            position: 0,
+            remaining: copies,
+        };
+        let result = time_snippet!(part2(BufReader::new(repeat))?);
+        println!("Stress Result = {}", result);
+        assert_eq!(48 + 40 * (copies as i64 - 1), result);
+    }
+
+    if cli_flag("list") {
+        let mut set = InstructionSet::new().with(Mul).with(Do).with(Dont);
+        if cli_flag("add") {
+            set = set.with(Add);
+        }
+        if cli_flag("sub") {
+            set = set.with(Sub);
+        }
+        if let Some(digits) = cli_option("max-digits") {
+            set = set.with_digits(1..=digits.parse()?);
+        }
+
+        let input = BufReader::new(File::open(INPUT_FILE)?);
+        let total = set.run_reader(input, |entry| println!("{}", entry))?;
+        println!("Total = {}", total);
+    }
+
     Ok(())
 }
 
-fn parse(input: &str) -> Result<i64> {
-    let regex = Regex::new(r"mul\((?<first>\d{1,3}),(?<second>\d{1,3})\)")?;
-    let mut it = regex.captures_iter(input);
+/// What the instructions work on.
+#[derive(Debug, Clone)]
+struct State {
+    enabled: bool,
+    total: i64,
+}
+
+/// An instruction written `name(argument,...)` in the corrupted memory.
+trait Instruction {
+    fn name(&self) -> &'static str;
+
+    fn arity(&self) -> usize;
+
+    /// Runs the instruction, returning false when the state makes it skip. Fails when the
+    /// total does not fit anymore.
+    fn execute(&self, arguments: &[i64], state: &mut State) -> Result<bool>;
+}
+
+/// Adds `value` to the total of an enabled state.
+fn accumulate(state: &mut State, value: Option<i64>, instruction: &str) -> Result<bool> {
+    if state.enabled {
+        state.total = value
+            .and_then(|value| state.total.checked_add(value))
+            .ok_or_else(|| anyhow!("{} overflows the total {}", instruction, state.total))?;
+    }
+    Ok(state.enabled)
+}
+
+struct Mul;
+
+impl Instruction for Mul {
+    fn name(&self) -> &'static str {
+        "mul"
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn execute(&self, arguments: &[i64], state: &mut State) -> Result<bool> {
+        let value = arguments[0].checked_mul(arguments[1]);
+        accumulate(state, value, &format!("{}{:?}", self.name(), arguments))
+    }
+}
+
+struct Add;
+
+impl Instruction for Add {
+    fn name(&self) -> &'static str {
+        "add"
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn execute(&self, arguments: &[i64], state: &mut State) -> Result<bool> {
+        let value = arguments[0].checked_add(arguments[1]);
+        accumulate(state, value, &format!("{}{:?}", self.name(), arguments))
+    }
+}
+
+struct Sub;
+
+impl Instruction for Sub {
+    fn name(&self) -> &'static str {
+        "sub"
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn execute(&self, arguments: &[i64], state: &mut State) -> Result<bool> {
+        let value = arguments[0].checked_sub(arguments[1]);
+        accumulate(state, value, &format!("{}{:?}", self.name(), arguments))
+    }
+}
+
+struct Do;
+
+impl Instruction for Do {
+    fn name(&self) -> &'static str {
+        "do"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn execute(&self, _: &[i64], state: &mut State) -> Result<bool> {
+        state.enabled = true;
+        Ok(true)
+    }
+}
+
+struct Dont;
+
+impl Instruction for Dont {
+    fn name(&self) -> &'static str {
+        "don't"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn execute(&self, _: &[i64], state: &mut State) -> Result<bool> {
+        state.enabled = false;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    /// Index of the instruction in its set, and its arguments.
+    Instruction(usize, Vec<i64>),
+    Noise,
+}
+
+/// A piece of the input, with its byte span.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+/// What the bytes at some position hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Scan {
+    /// An instruction, its arguments and its length.
+    Found(usize, Vec<i64>, usize),
+    NotFound,
+    /// The start of an instruction cut by the end of the input.
+    Incomplete,
+}
+
+/// The instructions to look for, and how many digits their arguments may have.
+struct InstructionSet {
+    instructions: Vec<Box<dyn Instruction>>,
+    digits: std::ops::RangeInclusive<usize>,
+}
 
-    let mut total = 0i64;
-    while let Some(capture) = it.next() {
-        let first= &capture["first"].parse::<i64>()?;
-        let second= &capture["second"].parse::<i64>()?;
-        total = total + (first*second);
+impl InstructionSet {
+    fn new() -> Self {
+        InstructionSet {
+            instructions: Vec::new(),
+            digits: 1..=3,
+        }
     }
 
-    Ok(total)
+    /// Adds an instruction, keeping longer names first so `don't()` is not cut short by `do`.
+    fn with(mut self, instruction: impl Instruction + 'static) -> Self {
+        let position = self
+            .instructions
+            .partition_point(|i| i.name().len() >= instruction.name().len());
+        self.instructions.insert(position, Box::new(instruction));
+        self
+    }
+
+    /// Sets how many digits arguments may have, at most 18 so any of them fits an `i64`.
+    fn with_digits(mut self, digits: std::ops::RangeInclusive<usize>) -> Self {
+        self.digits = *digits.start()..=(*digits.end()).min(MAX_DIGITS);
+        self
+    }
+
+    /// Looks for any of the instructions starting at `position`.
+    fn scan(&self, bytes: &[u8], position: usize) -> Scan {
+        let mut incomplete = false;
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            match self.scan_instruction(index, instruction.as_ref(), &bytes[position..]) {
+                Scan::Incomplete => incomplete = true,
+                Scan::NotFound => {}
+                found => return found,
+            }
+        }
+
+        if incomplete {
+            Scan::Incomplete
+        } else {
+            Scan::NotFound
+        }
+    }
+
+    fn scan_instruction(&self, index: usize, instruction: &dyn Instruction, bytes: &[u8]) -> Scan {
+        let mut position = 0;
+
+        // The name and the opening parenthesis
+        for byte in instruction.name().bytes().chain(std::iter::once(b'(')) {
+            match bytes.get(position) {
+                None => return Scan::Incomplete,
+                Some(&b) if b != byte => return Scan::NotFound,
+                _ => position += 1,
+            }
+        }
+
+        let mut arguments = Vec::with_capacity(instruction.arity());
+        for i in 0..instruction.arity() {
+            let digits = bytes[position..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            if position + digits == bytes.len() {
+                // Cut within the digits, unless there are already too many of them
+                return if digits <= *self.digits.end() {
+                    Scan::Incomplete
+                } else {
+                    Scan::NotFound
+                };
+            }
+            if !self.digits.contains(&digits) {
+                return Scan::NotFound;
+            }
+
+            let text = std::str::from_utf8(&bytes[position..position + digits]).unwrap();
+            match text.parse() {
+                std::result::Result::Ok(value) => arguments.push(value),
+                Err(_) => return Scan::NotFound,
+            }
+            position += digits;
+
+            let separator = if i + 1 < instruction.arity() {
+                b','
+            } else {
+                b')'
+            };
+            match bytes.get(position) {
+                None => return Scan::Incomplete,
+                Some(&b) if b != separator => return Scan::NotFound,
+                _ => position += 1,
+            }
+        }
+
+        if instruction.arity() == 0 {
+            match bytes.get(position) {
+                None => return Scan::Incomplete,
+                Some(b')') => position += 1,
+                _ => return Scan::NotFound,
+            }
+        }
+
+        Scan::Found(index, arguments, position)
+    }
+
+    fn tokenize<'a>(&'a self, input: &'a str) -> Tokenizer<'a> {
+        Tokenizer {
+            set: self,
+            bytes: input.as_bytes(),
+            position: 0,
+            end_of_input: true,
+        }
+    }
+
+    /// Runs every instruction of the input in order, starting enabled.
+    fn run(&self, input: &str) -> Result<Run> {
+        let mut entries = Vec::new();
+        let total = self.run_reader(input.as_bytes(), |entry| entries.push(entry))?;
+        Ok(Run { total, entries })
+    }
+
+    /// Runs the instructions straight from a reader, one chunk at a time. Only the tail of a
+    /// chunk that could still become an instruction is carried over to the next one, so the
+    /// memory used does not grow with the input. Each instruction met goes to `on_entry`.
+    fn run_reader(&self, mut reader: impl BufRead, mut on_entry: impl FnMut(Entry)) -> Result<i64> {
+        let mut state = State {
+            enabled: true,
+            total: 0,
+        };
+        let mut buffer: Vec<u8> = Vec::new();
+        // Offset in the input of the first byte of the buffer
+        let mut offset = 0;
+
+        loop {
+            let chunk = reader.fill_buf()?;
+            let end_of_input = chunk.is_empty();
+            buffer.extend_from_slice(chunk);
+            let length = chunk.len();
+            reader.consume(length);
+
+            let mut tokens = Tokenizer {
+                set: self,
+                bytes: &buffer,
+                position: 0,
+                end_of_input,
+            };
+            for token in &mut tokens {
+                if let TokenKind::Instruction(index, arguments) = token.kind {
+                    let instruction = &self.instructions[index];
+                    let accepted = instruction.execute(&arguments, &mut state)?;
+                    on_entry(Entry {
+                        name: instruction.name(),
+                        arguments,
+                        span: offset + token.span.start..offset + token.span.end,
+                        accepted,
+                    });
+                }
+            }
+
+            let position = tokens.position;
+            buffer.drain(..position);
+            offset += position;
+            if end_of_input {
+                return Ok(state.total);
+            }
+        }
+    }
+}
+
+/// The same bytes over and over, to stress the streaming run without storing the input.
+struct Repeat<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    remaining: usize,
+}
+
+impl Read for Repeat<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            return std::io::Result::Ok(0);
+        }
+
+        let length = buf.len().min(self.bytes.len() - self.position);
+        buf[..length].copy_from_slice(&self.bytes[self.position..self.position + length]);
+        self.position += length;
+        if self.position == self.bytes.len() {
+            self.position = 0;
+            self.remaining -= 1;
+        }
+        std::io::Result::Ok(length)
+    }
 }
 
-fn parse_with_do_and_dont(input: &str) -> Result<i64> {
+/// Splits the input into instructions and the noise between them. Unless the bytes are the
+/// end of the input, it stops before an instruction they cut short.
+struct Tokenizer<'a> {
+    set: &'a InstructionSet,
+    bytes: &'a [u8],
+    position: usize,
+    end_of_input: bool,
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.position;
+
+        while self.position < self.bytes.len() {
+            match self.set.scan(self.bytes, self.position) {
+                // Hand out the noise first, the instruction comes next time
+                Scan::Found(..) if self.position > start => break,
+                Scan::Found(index, arguments, length) => {
+                    self.position += length;
+                    return Some(Token {
+                        kind: TokenKind::Instruction(index, arguments),
+                        span: start..self.position,
+                    });
+                }
+                // Wait for the rest of the instruction
+                Scan::Incomplete if !self.end_of_input => break,
+                _ => self.position += 1,
+            }
+        }
 
-    let mut total = 0i64;
-    let do_strings: Vec<&str> = input.split("do()").collect();
-    for do_item in do_strings {
-        let dont_string: Vec<&str> = do_item.split("don't()").collect();
-        let do_string_to_use = dont_string[0];
-        let string_value = parse(do_string_to_use)?;
-        total = total + string_value;
+        if self.position == start {
+            return None;
+        }
+        Some(Token {
+            kind: TokenKind::Noise,
+            span: start..self.position,
+        })
     }
+}
+
+/// One instruction met while running, and whether it did anything.
+#[derive(Debug, Clone)]
+struct Entry {
+    name: &'static str,
+    arguments: Vec<i64>,
+    span: Range<usize>,
+    accepted: bool,
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>6}..{:<6} {:<8} {}({})",
+            self.span.start,
+            self.span.end,
+            if self.accepted { "accepted" } else { "skipped" },
+            self.name,
+            self.arguments.iter().join(",")
+        )
+    }
+}
 
-    Ok(total)
+#[derive(Debug, Clone)]
+struct Run {
+    total: i64,
+    entries: Vec<Entry>,
 }
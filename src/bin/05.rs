@@ -2,8 +2,10 @@ use adv_code_2024::*;
 use anyhow::*;
 use code_timing_macros::time_snippet;
 use const_format::concatcp;
-use itertools::{all, Itertools};
-use std::collections::HashMap;
+use itertools::Itertools;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 
@@ -86,124 +88,117 @@ fn main() -> Result<()> {
         let result = filter_invalid_manuals(&parsing)?;
 
         let mut total = 0;
-        for mut invalid_manual in result.invalid_manuals {
-            while !fix_manual_2(&parsing, &mut invalid_manual)? {
-                
-            }
-            total += invalid_manual[invalid_manual.len() / 2]
+        for invalid_manual in result.invalid_manuals {
+            let fixed_manual = parsing.rules.repair(&invalid_manual)?;
+            total += fixed_manual[fixed_manual.len() / 2]
         }
-        
+
         Ok(total)
     }
 
     let result = part1(TEST)?;
     println!("Test Result 1 = {}", result);
     assert_eq!(143, result);
-    
+
     let mut input_file = File::open(INPUT_FILE)?;
     let mut buffer = Vec::new();
     input_file.read_to_end(&mut buffer)?;
     let input = String::from_utf8_lossy(&buffer);
-    
+
     let result = time_snippet!(part1(&input)?);
     println!("Result 1 = {}", result);
-    
+
     println!("=== Part 2 ===");
 
     let result = part2(TEST)?;
     println!("Test Result 2 = {}", result);
     assert_eq!(123, result);
-    
-    let result = time_snippet!(part2(&input)?);
-    println!("Result 2 = {}", result);
-
-    Ok(())
-}
 
-struct Manuals {
-    valid_manuals: Vec<Vec<u32>>,
-    invalid_manuals: Vec<Vec<u32>>,
-}
-
-fn fix_manual_2(data: &Stuff, manual: &mut Vec<u32>) -> Result<bool> {
-    
-    for index in 0..manual.len() {
-        let page = manual[index];
+    let parsing = parse(TEST2)?;
+    let fixed_manuals = parsing
+        .manuals
+        .iter()
+        .map(|manual| parsing.rules.repair(manual))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(
+        vec![
+            vec![97, 75, 47, 29, 13],
+            vec![97, 75, 47, 61, 53],
+            vec![61, 29, 13]
+        ],
+        fixed_manuals
+    );
+
+    let parsing = parse(TEST)?;
+    let reports = parsing
+        .manuals
+        .iter()
+        .map(|manual| parsing.rules.report(manual))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(3, reports.iter().flatten().count());
+    println!("{}", Report::header());
+    reports
+        .iter()
+        .flatten()
+        .for_each(|report| print!("{}", report));
+
+    let report = reports[3].as_ref().unwrap();
+    let violation = Violation {
+        before: 97,
+        after: 75,
+        before_position: 1,
+        after_position: 0,
+    };
+    assert_eq!(vec![violation], report.violations);
+    assert_eq!(vec![97, 75, 47, 61, 53], report.fixed);
+    assert_eq!(
+        vec![Move {
+            page: 97,
+            from: 1,
+            to: 0
+        }],
+        report.moves
+    );
+
+    // 97,13,75,29,47 breaks 75|13, 29|13, 47|13 and 47|29, and two moves fix it
+    let report = reports[5].as_ref().unwrap();
+    let rules = report
+        .violations
+        .iter()
+        .map(|v| (v.before, v.after))
+        .collect_vec();
+    assert_eq!(vec![(75, 13), (29, 13), (47, 13), (47, 29)], rules);
+    assert_eq!(2, report.moves.len());
+
+    // Cyclic rules are reported instead of looping forever
+    let parsing = parse("1|2\n2|3\n3|1\n\n1,2,3\n3,1\n")?;
+    let error = parsing.rules.repair(&parsing.manuals[0]).unwrap_err();
+    println!("{}", error);
+    assert_eq!(
+        "Rules form a cycle between pages 1 -> 2 -> 3 -> 1",
+        error.to_string()
+    );
+    assert_eq!(vec![3, 1], parsing.rules.repair(&parsing.manuals[1])?);
 
-        for left_index in 0..index {
-            let page_at_left = manual[left_index];
-            if !is_valid_before(&data.rules, &page, &page_at_left) {
-                manual.swap(index, left_index);
-                return Ok(false);
-            }
-        }
+    let result = time_snippet!(part2(&input)?);
+    println!("Result 2 = {}", result);
 
-        for right_index in (index + 1)..manual.len() - 1 {
-            let page_at_right = manual[right_index];
-            if !is_valid_after(&data.rules, &page, &page_at_right) {
-                manual.swap(index, right_index);
-                return Ok(false);
+    if cli_flag("report") {
+        let parsing = parse(&input)?;
+        println!("{}", Report::header());
+        for manual in parsing.manuals.iter() {
+            if let Some(report) = parsing.rules.report(manual)? {
+                print!("{}", report);
             }
         }
     }
 
-    Ok(true)
+    Ok(())
 }
 
-fn fix_manual(data: &Stuff, manual: &Vec<u32>) -> Result<u32> {
-
-    let mut manual_to_fix = manual.clone();
-    let mut counter = 0;
-    
-    while !check_manual(data, &manual_to_fix)? {
-        
-        let mut main_page_index = 0;
-
-        loop {
-            if main_page_index > manual_to_fix.len() - 2 {
-                break;
-            }
-
-            //println!("{:#?}", manual_to_fix);
-
-            let mut page_index = main_page_index;
-            let mut next_page_index = main_page_index + 1;
-
-            let mut page = manual_to_fix[page_index];
-            let mut page_at_right = manual_to_fix[next_page_index];
-
-            loop {
-                //println!("checking {} vs {}", page, page_at_right);
-                if is_valid_after(&data.rules, &page, &page_at_right) {
-                    //println!("ok having {} before {}", page, page_at_right);
-                } else {
-                    //println!("swapping {} with {}", page, page_at_right);
-                    manual_to_fix.swap(page_index, next_page_index);
-                }
-
-                page_index += 1;
-                next_page_index += 1;
-
-                if next_page_index > manual.len() - 1 {
-                    main_page_index += 1;
-                    break;
-                }
-
-                page = manual[page_index];
-                page_at_right = manual[next_page_index];
-            }
-        }
-
-        counter += 1;
-        if counter == 5 {
-            println!("{:#?}", manual);
-            println!("{:#?}", manual_to_fix);
-            panic!("too many cicles");
-        }
-        //println!("{:#?}", manual_to_fix);
-    }
-    
-    Ok(manual_to_fix[manual_to_fix.len() / 2])
+struct Manuals {
+    valid_manuals: Vec<Vec<u32>>,
+    invalid_manuals: Vec<Vec<u32>>,
 }
 
 fn filter_invalid_manuals(data: &Stuff) -> Result<Manuals> {
@@ -211,7 +206,7 @@ fn filter_invalid_manuals(data: &Stuff) -> Result<Manuals> {
     let mut invalid_manuals: Vec<Vec<u32>> = Vec::new();
 
     for manual in &data.manuals {
-        if check_manual(data, manual)? {
+        if data.rules.is_ordered(manual) {
             valid_manuals.push(manual.clone());
         } else {
             invalid_manuals.push(manual.clone());
@@ -235,88 +230,268 @@ fn sum_middle_pages(manuals: &Vec<Vec<u32>>) -> Result<u32> {
     Ok(total)
 }
 
-fn check_manual(data: &Stuff, manual: &[u32]) -> Result<bool> {
-    for index in 0..manual.len() {
-        let page = manual[index];
+/// Ordering rules as a graph, with an edge from X to Y for every `X|Y`.
+#[derive(Debug, Default)]
+struct RuleGraph {
+    successors: HashMap<u32, HashSet<u32>>,
+}
 
-        for left_index in 0..index {
-            let page_at_left = manual[left_index];
-            if !is_valid_before(&data.rules, &page, &page_at_left) {
-                return Ok(false);
-            }
+impl RuleGraph {
+    fn add_rule(&mut self, before: u32, after: u32) {
+        self.successors.entry(before).or_default().insert(after);
+    }
+
+    /// The rules between the pages of one update only, as edges between their positions.
+    fn induced(&self, manual: &[u32]) -> Vec<Vec<usize>> {
+        let positions: HashMap<u32, usize> =
+            manual.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+        manual
+            .iter()
+            .map(|page| {
+                self.successors
+                    .get(page)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|after| positions.get(after).copied())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Linear in the pages and the rules between them: every edge has to point forward.
+    fn is_ordered(&self, manual: &[u32]) -> bool {
+        self.induced(manual)
+            .iter()
+            .enumerate()
+            .all(|(from, edges)| edges.iter().all(|&to| from < to))
+    }
+
+    /// Topological sort of the update, keeping pages in their original order whenever the
+    /// rules allow it, so a valid update comes back unchanged.
+    fn repair(&self, manual: &[u32]) -> Result<Vec<u32>> {
+        let edges = self.induced(manual);
+        let mut incoming = vec![0; manual.len()];
+        for &to in edges.iter().flatten() {
+            incoming[to] += 1;
         }
 
-        for right_index in (index + 1)..manual.len() - 1 {
-            let page_at_right = manual[right_index];
-            if !is_valid_after(&data.rules, &page, &page_at_right) {
-                return Ok(false);
+        let mut ready: BTreeSet<usize> = (0..manual.len()).filter(|&i| incoming[i] == 0).collect();
+        let mut fixed = Vec::with_capacity(manual.len());
+        while let Some(from) = ready.pop_first() {
+            fixed.push(manual[from]);
+            for &to in edges[from].iter() {
+                incoming[to] -= 1;
+                if incoming[to] == 0 {
+                    ready.insert(to);
+                }
             }
         }
+
+        if fixed.len() < manual.len() {
+            let cycle = Self::find_cycle(&edges, &incoming);
+            bail!(
+                "Rules form a cycle between pages {}",
+                cycle.iter().map(|&i| manual[i]).join(" -> ")
+            );
+        }
+
+        Ok(fixed)
     }
 
-    Ok(true)
-}
+    /// Every rule the update breaks, in the order of the pages it puts first.
+    fn violations(&self, manual: &[u32]) -> Vec<Violation> {
+        let mut violations = self
+            .induced(manual)
+            .iter()
+            .enumerate()
+            .flat_map(|(from, edges)| {
+                edges
+                    .iter()
+                    .filter(move |&&to| to < from)
+                    .map(move |&to| Violation {
+                        before: manual[from],
+                        after: manual[to],
+                        before_position: from,
+                        after_position: to,
+                    })
+            })
+            .collect_vec();
+        violations.sort_by_key(|v| (v.after_position, v.before_position));
+        violations
+    }
+
+    /// Why an update is rejected and how it gets fixed, or None when it is already ordered.
+    fn report(&self, manual: &[u32]) -> Result<Option<Report>> {
+        let violations = self.violations(manual);
+        if violations.is_empty() {
+            return Ok(None);
+        }
+
+        let fixed = self.repair(manual)?;
+        let moves = minimal_moves(manual, &fixed);
+        Ok(Some(Report {
+            manual: manual.to_vec(),
+            violations,
+            fixed,
+            moves,
+        }))
+    }
+
+    /// Pages left with incoming edges by the sort all sit after a cycle, so walking backwards
+    /// along those edges has to come back to a page already seen.
+    fn find_cycle(edges: &[Vec<usize>], incoming: &[usize]) -> Vec<usize> {
+        let mut predecessor = vec![None; edges.len()];
+        for (from, to_list) in edges.iter().enumerate() {
+            for &to in to_list.iter().filter(|_| incoming[from] > 0) {
+                predecessor[to] = Some(from);
+            }
+        }
 
-fn is_valid_before(rules: &HashMap<u32, Vec<u32>>, page: &u32, page_before: &u32) -> bool {
-    if let Some(rule) = rules.get(page) {
-        if rule.contains(page_before) {
-            return false;
+        let mut walk = vec![(0..edges.len()).find(|&i| incoming[i] > 0).unwrap()];
+        loop {
+            let previous = predecessor[*walk.last().unwrap()].unwrap();
+            if let Some(start) = walk.iter().position(|&i| i == previous) {
+                let mut cycle = walk[start..].to_vec();
+                cycle.reverse();
+                // Start from the earliest page of the update, and close the loop
+                let first = cycle.iter().position_min().unwrap();
+                cycle.rotate_left(first);
+                cycle.push(cycle[0]);
+                return cycle;
+            }
+            walk.push(previous);
         }
     }
+}
+
+/// A broken `before|after` rule, with the positions of both pages in the update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Violation {
+    before: u32,
+    after: u32,
+    before_position: usize,
+    after_position: usize,
+}
+
+/// One page taken out of the update and put back at its place in the repaired one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Move {
+    page: u32,
+    from: usize,
+    to: usize,
+}
+
+#[derive(Debug)]
+struct Report {
+    manual: Vec<u32>,
+    violations: Vec<Violation>,
+    fixed: Vec<u32>,
+    moves: Vec<Move>,
+}
 
-    true
+impl Report {
+    fn header() -> String {
+        format!(
+            "{:<30} {:<8} {:>4} {:>4}  {}",
+            "Update", "Rule", "X at", "Y at", "Repair"
+        )
+    }
 }
 
-fn is_valid_after(rules: &HashMap<u32, Vec<u32>>, page: &u32, page_after: &u32) -> bool {
-    if let Some(rule) = rules.get(page) {
-        if rule.contains(page_after) {
-            return true;
+/// One row per violated rule, positions counted from 1, and the moves on the first row.
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let moves = self
+            .moves
+            .iter()
+            .map(|m| format!("{} {}->{}", m.page, m.from + 1, m.to + 1))
+            .join(", ");
+
+        for (i, violation) in self.violations.iter().enumerate() {
+            let update = if i == 0 {
+                self.manual.iter().join(",")
+            } else {
+                String::new()
+            };
+            let repair = if i == 0 { moves.as_str() } else { "" };
+            let row = format!(
+                "{:<30} {:<8} {:>4} {:>4}  {}",
+                update,
+                format!("{}|{}", violation.before, violation.after),
+                violation.before_position + 1,
+                violation.after_position + 1,
+                repair
+            );
+            writeln!(f, "{}", row.trim_end())?;
         }
+        fmt::Result::Ok(())
     }
+}
 
-    if let Some(rule) = rules.get(page_after) {
-        if rule.contains(page) {
-            return false;
+/// The fewest pages to move to turn `manual` into `fixed`: everything outside the longest
+/// run of pages both keep in the same relative order.
+fn minimal_moves(manual: &[u32], fixed: &[u32]) -> Vec<Move> {
+    let from: HashMap<u32, usize> = manual.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+    let order = fixed.iter().map(|page| from[page]).collect_vec();
+
+    // Longest increasing subsequence of the original positions, quadratic as updates are short
+    let mut length = vec![1; order.len()];
+    let mut previous = vec![None; order.len()];
+    for i in 0..order.len() {
+        for j in 0..i {
+            // On ties keep the page that came first, so the one jumping ahead is moved
+            let better = length[j] + 1 > length[i]
+                || (length[j] + 1 == length[i] && previous[i].is_some_and(|p| order[j] < order[p]));
+            if order[j] < order[i] && better {
+                length[i] = length[j] + 1;
+                previous[i] = Some(j);
+            }
         }
     }
 
-    true
+    let mut kept = HashSet::new();
+    let mut current = length.iter().position_max();
+    while let Some(i) = current {
+        kept.insert(i);
+        current = previous[i];
+    }
+
+    (0..fixed.len())
+        .filter(|i| !kept.contains(i))
+        .map(|to| Move {
+            page: fixed[to],
+            from: order[to],
+            to,
+        })
+        .collect()
 }
 
 struct Stuff {
-    rules: HashMap<u32, Vec<u32>>,
+    rules: RuleGraph,
     manuals: Vec<Vec<u32>>,
 }
 
 fn parse(input: &str) -> Result<Stuff> {
-    let mut rules: HashMap<u32, Vec<u32>> = HashMap::new();
-
-    let input_split: Vec<&str> = input.split("\n\n").filter(|&s| !s.is_empty()).collect();
+    let mut rules = RuleGraph::default();
 
-    let rules_to_parse = input_split[0];
-    let manuals_to_parse = input_split[1];
+    let input = input.replace("\r\n", "\n");
+    let (rules_to_parse, manuals_to_parse) = input
+        .split_once("\n\n")
+        .ok_or_else(|| anyhow!("Missing the empty line between the rules and the updates"))?;
 
-    // sort rules
     for rule in rules_to_parse.lines() {
-        let rule_split: Vec<&str> = rule.split('|').collect();
-        let first: u32 = rule_split[0].parse()?;
-        let second: u32 = rule_split[1].parse()?;
-
-        if let std::collections::hash_map::Entry::Vacant(e) = rules.entry(first) {
-            e.insert(vec![second]);
-        } else {
-            let content = rules.get_mut(&first).unwrap();
-            content.push(second);
-            content.sort();
-        }
+        let (first, second) = rule
+            .split_once('|')
+            .ok_or_else(|| anyhow!("Rule '{}' is not like 'X|Y'", rule))?;
+        rules.add_rule(first.trim().parse()?, second.trim().parse()?);
     }
 
     let mut manuals: Vec<Vec<u32>> = Vec::new();
-    for manual in manuals_to_parse.lines() {
-        let pages: Vec<u32> = manual
+    for manual in manuals_to_parse.lines().filter(|l| !l.trim().is_empty()) {
+        let pages = manual
             .split(',')
-            .map(|p| p.parse::<u32>().unwrap())
-            .collect();
+            .map(|p| p.trim().parse::<u32>())
+            .collect::<std::result::Result<Vec<u32>, _>>()?;
         manuals.push(pages);
     }
 
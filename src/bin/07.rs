@@ -2,10 +2,12 @@ use adv_code_2024::*;
 use anyhow::*;
 use code_timing_macros::time_snippet;
 use const_format::concatcp;
-use itertools::{iproduct, Itertools};
+use itertools::Itertools;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
-use std::iter::successors;
+use std::sync::Arc;
 use tokio::task::JoinSet;
 
 const DAY: &str = "07";
@@ -33,15 +35,15 @@ async fn main() -> Result<()> {
     //region Part 1
     println!("=== Part 1 ===");
 
-    async fn part1(input: &str) -> Result<usize> {
+    async fn part1(input: &str) -> Result<u64> {
         let matrix = parse(input)?;
-        let result = process_matrix(&matrix, false).await?;
+        let result = process_matrix(&matrix, &Operators::new().with(Add).with(Mul)).await?;
         Ok(result)
     }
 
-    async fn part2(input: &str) -> Result<usize> {
+    async fn part2(input: &str) -> Result<u64> {
         let matrix = parse(input)?;
-        let result = process_matrix(&matrix, true).await?;
+        let result = process_matrix(&matrix, &Operators::standard()).await?;
         Ok(result)
     }
 
@@ -49,89 +51,458 @@ async fn main() -> Result<()> {
     println!("Test Result 1 = {}", result);
     assert_eq!(3749, result);
 
+    // Each solved equation carries the operators that reach its target
+    let equations = parse(TEST)?;
+    let plain = Operators::parse("+,*")?;
+    let solved = equations
+        .iter()
+        .filter_map(|eq| eq.solve(&plain))
+        .inspect(|s| assert_eq!(Some(s.equation.target), s.equation.evaluate(&s.ops)))
+        .map(|s| s.to_string())
+        .collect_vec();
+    assert_eq!(
+        vec![
+            "190 = 10 * 19",
+            "3267 = 81 * 40 + 27",
+            "292 = 11 + 6 * 16 + 20"
+        ],
+        solved
+    );
+
+    // Large values neither overflow nor match by accident
+    let huge = parse("18446744073709551615: 18446744073709551615 2\n")?;
+    assert!(huge[0].solve(&Operators::standard()).is_none());
+    assert_eq!(None, huge[0].evaluate(&Operators::parse("*")?.ops));
+    let zero = parse("0: 5 0 0")?[0].solve(&plain).unwrap();
+    assert_eq!("0 = 5 * 0 + 0", zero.to_string());
+    assert!(parse("12 3 4").is_err());
+    assert!(parse("12: 3 x").is_err());
+
     let mut input_file = File::open(INPUT_FILE)?;
     let mut buffer = Vec::new();
     input_file.read_to_end(&mut buffer)?;
     let input = String::from_utf8_lossy(&buffer);
-    
+
     let result = time_snippet!(part1(&input).await?);
     println!("Result 1 = {}", result);
-    
+
     println!("=== Part 2 ===");
-    
+
     let result = part2(TEST).await?;
     println!("Test Result 2 = {}", result);
     assert_eq!(11387, result);
-    
+    assert_eq!(0, part2(TEST2).await?);
+
+    let solution = parse("7290: 6 8 6 15")?[0].solve(&Operators::standard());
+    assert_eq!("7290 = 6 * 8 || 6 * 15", solution.unwrap().to_string());
+
+    // Other operators, with or without an inverse
+    for (ops, equation, expected) in [
+        ("+,*,-", "5: 10 5", "5 = 10 - 5"),
+        ("*,^", "81: 3 2 2", "81 = 3 ^ 2 ^ 2"),
+        ("+,||2", "11: 5 1", "11 = 5 ||2 1"),
+        ("+,||16", "4095: 15 255", "4095 = 15 ||16 255"),
+    ] {
+        let solution = parse(equation)?[0].solve(&Operators::parse(ops)?);
+        assert_eq!(expected, solution.unwrap().to_string());
+    }
+    assert!(Operators::parse("+,||1").is_err());
+    assert!(Operators::parse("+,%").is_err());
+
     let result = time_snippet!(part2(&input).await?);
     println!("Result 2 = {}", result);
 
+    let operators = match cli_option("ops") {
+        Some(spec) => {
+            let operators = Operators::parse(&spec)?;
+            let result = process_matrix(&parse(&input)?, &operators).await?;
+            println!("Result with {} = {}", operators, result);
+            operators
+        }
+        None => Operators::standard(),
+    };
+
+    if cli_flag("explain") {
+        for eq in parse(&input)? {
+            if let Some(solution) = process_row(eq, operators.clone()).await {
+                println!("{}", solution);
+            }
+        }
+    }
+
     Ok(())
 }
 
-async fn process_matrix(matrix: &[Vec<usize>], enable_pipe: bool)
-    -> Result<usize> {
-
+async fn process_matrix(matrix: &[Equation], operators: &Operators) -> Result<u64> {
     let mut set = JoinSet::new();
     for eq in matrix {
-        set.spawn(process_row(eq.clone(), enable_pipe));
+        set.spawn(process_row(eq.clone(), operators.clone()));
     }
     let output = set.join_all().await;
 
-    let total = output.iter().sum();
+    let total = output.iter().flatten().map(|s| s.equation.target).sum();
     Ok(total)
 }
 
-async fn process_row(equation: Vec<usize>, enable_pipe: bool) -> usize {
+async fn process_row(equation: Equation, operators: Operators) -> Option<Solution> {
+    equation.solve(&operators)
+}
+
+/// How the left operand of `left op y == target` can be recovered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Inverse {
+    /// Only this left operand works
+    Left(u64),
+    /// Any left operand works
+    Any,
+    /// No left operand works
+    Never,
+    /// The operator cannot tell, the left side has to be evaluated forwards
+    Unknown,
+}
+
+/// A binary operator of the calibration equations, evaluated left to right.
+trait Operator: fmt::Debug + Send + Sync {
+    fn symbol(&self) -> String;
+
+    /// Forward evaluation, `None` when the result does not fit.
+    fn apply(&self, x: u64, y: u64) -> Option<u64>;
+
+    fn invert(&self, _target: u64, _y: u64) -> Inverse {
+        Inverse::Unknown
+    }
+}
+
+fn left(value: Option<u64>) -> Inverse {
+    value.map_or(Inverse::Never, Inverse::Left)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Add;
 
-    let size = equation.len() - 1; // first is the result
-    let expected_result = equation[0];
-    
-    let calc = |x:usize, y:usize, op: char| -> usize {
-        if op == '+' {
-            x + y
-        } else if op == '*' {
-            x * y
-        } else {
-            format!("{}{}", x, y).parse().unwrap()
+impl Operator for Add {
+    fn symbol(&self) -> String {
+        "+".to_string()
+    }
+
+    fn apply(&self, x: u64, y: u64) -> Option<u64> {
+        x.checked_add(y)
+    }
+
+    fn invert(&self, target: u64, y: u64) -> Inverse {
+        left(target.checked_sub(y))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Mul;
+
+impl Operator for Mul {
+    fn symbol(&self) -> String {
+        "*".to_string()
+    }
+
+    fn apply(&self, x: u64, y: u64) -> Option<u64> {
+        x.checked_mul(y)
+    }
+
+    fn invert(&self, target: u64, y: u64) -> Inverse {
+        match (target, y) {
+            (0, 0) => Inverse::Any,
+            (_, 0) => Inverse::Never,
+            _ if target.is_multiple_of(y) => Inverse::Left(target / y),
+            _ => Inverse::Never,
         }
-    };
-    
-    let operators = if enable_pipe { "*+|".chars() } else { "*+".chars() };
-    
-    for symbols in (1..=size-1).map(|_| operators.clone()).multi_cartesian_product()  {
-        // println!("Testing combination {:?}", symbols);
-        let mut combination_result = calc(equation[1], equation[2], symbols[0]);
-        
-        for i in 1..symbols.len() {
-            combination_result = calc(combination_result, equation[i + 2], symbols[i]);
+    }
+}
+
+/// Appends the digits of the right operand, written in `base`, to the left one.
+#[derive(Debug, Clone, Copy)]
+struct Concat {
+    base: u64,
+}
+
+impl Concat {
+    const DECIMAL: Concat = Concat { base: 10 };
+
+    /// The power of the base the left operand has to be shifted by to append `y`.
+    fn shift(&self, y: u64) -> u64 {
+        let mut shift = self.base;
+        while shift <= y {
+            match shift.checked_mul(self.base) {
+                Some(next) => shift = next,
+                None => return u64::MAX,
+            }
         }
+        shift
+    }
+}
 
-        // println!("Result is {:?}", combination_result);
-        if combination_result == expected_result {
-            return combination_result;
+impl Operator for Concat {
+    fn symbol(&self) -> String {
+        match self.base {
+            10 => "||".to_string(),
+            base => format!("||{}", base),
         }
     }
 
-    0
+    fn apply(&self, x: u64, y: u64) -> Option<u64> {
+        x.checked_mul(self.shift(y))?.checked_add(y)
+    }
+
+    fn invert(&self, target: u64, y: u64) -> Inverse {
+        let shift = self.shift(y);
+        match target % shift == y {
+            true => Inverse::Left(target / shift),
+            false => Inverse::Never,
+        }
+    }
 }
 
-fn parse(input: &str) -> Result<Vec<Vec<usize>> > {
-    let mut result: Vec<Vec<usize>> = Vec::new();
+/// Subtraction, only as long as the result stays non negative.
+#[derive(Debug, Clone, Copy)]
+struct Sub;
 
-    for line in input.lines() {
-        if line.is_empty(){
-            break;
+impl Operator for Sub {
+    fn symbol(&self) -> String {
+        "-".to_string()
+    }
+
+    fn apply(&self, x: u64, y: u64) -> Option<u64> {
+        x.checked_sub(y)
+    }
+
+    fn invert(&self, target: u64, y: u64) -> Inverse {
+        left(target.checked_add(y))
+    }
+}
+
+/// Exponentiation, with no inverse so that the left side is evaluated forwards.
+#[derive(Debug, Clone, Copy)]
+struct Pow;
+
+impl Operator for Pow {
+    fn symbol(&self) -> String {
+        "^".to_string()
+    }
+
+    fn apply(&self, x: u64, y: u64) -> Option<u64> {
+        x.checked_pow(y.try_into().ok()?)
+    }
+}
+
+/// The operators an equation may use, tried in order.
+#[derive(Debug, Clone, Default)]
+struct Operators {
+    ops: Vec<Arc<dyn Operator>>,
+}
+
+impl Operators {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// The operators of the puzzle: `+`, `*` and `||`.
+    fn standard() -> Self {
+        Self::new().with(Add).with(Mul).with(Concat::DECIMAL)
+    }
+
+    fn with(mut self, op: impl Operator + 'static) -> Self {
+        self.ops.push(Arc::new(op));
+        self
+    }
+
+    /// Builds a set from a comma separated list such as `+,*,||,-,^,||2`, where `||b`
+    /// concatenates in base `b`.
+    fn parse(spec: &str) -> Result<Self> {
+        let ops = Self::parse_list(spec)?;
+        if ops.is_empty() {
+            bail!("No operators in '{}'", spec);
+        }
+        Ok(Operators { ops })
+    }
+
+    fn parse_list(spec: &str) -> Result<Vec<Arc<dyn Operator>>> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let op: Arc<dyn Operator> = match name {
+                    "+" => Arc::new(Add),
+                    "*" => Arc::new(Mul),
+                    "-" => Arc::new(Sub),
+                    "^" => Arc::new(Pow),
+                    "||" => Arc::new(Concat::DECIMAL),
+                    _ => match name.strip_prefix("||").map(str::parse::<u64>) {
+                        Some(std::result::Result::Ok(base)) if base >= 2 => {
+                            Arc::new(Concat { base })
+                        }
+                        _ => bail!("Unknown operator '{}'", name),
+                    },
+                };
+                Ok(op)
+            })
+            .collect()
+    }
+}
+
+impl Display for Operators {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.ops.iter().map(|op| op.symbol()).join(","))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Equation {
+    target: u64,
+    operands: Vec<u64>,
+}
+
+impl Equation {
+    /// Evaluates the operands left to right with the given operators.
+    fn evaluate(&self, ops: &[Arc<dyn Operator>]) -> Option<u64> {
+        let (first, rest) = self.operands.split_first()?;
+        rest.iter()
+            .zip(ops)
+            .try_fold(*first, |acc, (&y, op)| op.apply(acc, y))
+    }
+
+    /// Finds operators making the equation true, working back from the target so that
+    /// each operator is only tried when it can be undone on the value left to reach.
+    /// Operators without an inverse fall back to evaluating the left side forwards.
+    fn solve(&self, operators: &Operators) -> Option<Solution> {
+        let last = self.operands.len().checked_sub(1)?;
+        let mut found = Vec::with_capacity(last);
+        if !self.solve_back(last, self.target, operators, &mut found) {
+            return None;
         }
+        found.reverse();
+        Some(Solution {
+            equation: self.clone(),
+            ops: found,
+        })
+    }
 
-        let mut row:Vec<usize> = Vec::new();
+    /// `found` holds the operators from the last one backwards.
+    fn solve_back(
+        &self,
+        index: usize,
+        target: u64,
+        operators: &Operators,
+        found: &mut Vec<Arc<dyn Operator>>,
+    ) -> bool {
+        if index == 0 {
+            return self.operands[0] == target;
+        }
+        let y = self.operands[index];
+        for op in &operators.ops {
+            found.push(op.clone());
+            let solved = match op.invert(target, y) {
+                Inverse::Left(left) => self.solve_back(index - 1, left, operators, found),
+                Inverse::Any => self.solve_forward(index - 1, operators, found, |_| true),
+                Inverse::Never => false,
+                Inverse::Unknown => self.solve_forward(index - 1, operators, found, |left| {
+                    op.apply(left, y) == Some(target)
+                }),
+            };
+            if solved {
+                return true;
+            }
+            found.pop();
+        }
+        false
+    }
 
-        let parsed_row = line.split(':').collect_vec();
-        row.push(parsed_row[0].parse()?);
-        parsed_row[1].split(' ').filter(|&i| !i.is_empty()).for_each(|i| row.push(i.parse().unwrap()));
+    /// Tries every way of evaluating the operands up to `last` until `accept` takes the value.
+    fn solve_forward(
+        &self,
+        last: usize,
+        operators: &Operators,
+        found: &mut Vec<Arc<dyn Operator>>,
+        accept: impl Fn(u64) -> bool,
+    ) -> bool {
+        let mut path = Vec::with_capacity(last);
+        if !self.forward(1, self.operands[0], last, operators, &mut path, &accept) {
+            return false;
+        }
+        found.extend(path.into_iter().rev());
+        true
+    }
 
-        result.push(row);
+    fn forward(
+        &self,
+        index: usize,
+        value: u64,
+        last: usize,
+        operators: &Operators,
+        path: &mut Vec<Arc<dyn Operator>>,
+        accept: &impl Fn(u64) -> bool,
+    ) -> bool {
+        if index > last {
+            return accept(value);
+        }
+        for op in &operators.ops {
+            if let Some(next) = op.apply(value, self.operands[index]) {
+                path.push(op.clone());
+                if self.forward(index + 1, next, last, operators, path, accept) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+}
+
+/// A solved equation, printed as the expression that reaches its target.
+#[derive(Debug, Clone)]
+struct Solution {
+    equation: Equation,
+    ops: Vec<Arc<dyn Operator>>,
+}
+
+impl Display for Solution {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} = {}",
+            self.equation.target, self.equation.operands[0]
+        )?;
+        for (op, y) in self.ops.iter().zip(&self.equation.operands[1..]) {
+            write!(f, " {} {}", op.symbol(), y)?;
+        }
+        fmt::Result::Ok(())
+    }
+}
+
+fn parse(input: &str) -> Result<Vec<Equation>> {
+    let mut result = Vec::new();
+
+    for (number, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (target, operands) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Line {} has no ':'", number + 1))?;
+        let target = target
+            .trim()
+            .parse()
+            .with_context(|| format!("Line {}: bad target '{}'", number + 1, target))?;
+        let operands = operands
+            .split_whitespace()
+            .map(|i| {
+                i.parse()
+                    .with_context(|| format!("Line {}: bad operand '{}'", number + 1, i))
+            })
+            .collect::<Result<Vec<u64>>>()?;
+        if operands.is_empty() {
+            bail!("Line {} has no operands", number + 1);
+        }
+
+        result.push(Equation { target, operands });
     }
 
     Ok(result)
-}
\ No newline at end of file
+}
@@ -1,3 +1,4 @@
+use adv_code_2024::animation::{Animation, Frame};
 use adv_code_2024::*;
 use anyhow::*;
 use code_timing_macros::time_snippet;
@@ -5,9 +6,10 @@ use const_format::concatcp;
 use image::{ImageBuffer, Rgb, RgbImage};
 use itertools::Itertools;
 use regex::Regex;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
-use tokio::task::JoinSet;
+use std::ops::Range;
 
 const DAY: &str = "14";
 const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
@@ -42,21 +44,74 @@ async fn main() -> Result<()> {
     println!("=== Part 1 ===");
 
     async fn part1(input: &str, max_width: usize, max_height: usize) -> Result<usize> {
-        let result = parse(input).await;
-        let total = execute_move(result, max_width, max_height).await;
-        Ok(total)
+        let arena = Arena::new(max_width, max_height, parse(input).await)?;
+        Ok(arena.safety_factor(MAX_SECONDS))
     }
 
     async fn part2(input: &str, max_width: usize, max_height: usize) -> Result<usize> {
-        let mut result = parse(input).await;
-        let total = look_for_easter_egg(&mut result, max_width, max_height).await;
-        Ok(total)
+        let arena = Arena::new(max_width, max_height, parse(input).await)?;
+        let score = match cli_option("score").as_deref() {
+            None | Some("variance") => Score::Variance,
+            Some("entropy") => Score::Entropy,
+            Some(other) => bail!("Unknown score '{}', use variance or entropy", other),
+        };
+        let second = look_for_easter_egg(&arena, score)?;
+
+        let frame = arena.positions(second);
+        println!("Largest cluster: {} robots", largest_cluster(&frame));
+
+        if let Some(path) = cli_option("save-image") {
+            save_frame(&frame, max_width, max_height, &path)?;
+        }
+
+        if let Some(path) = cli_option("animate") {
+            let seconds = match cli_option("frames") {
+                Some(value) => value.parse()?,
+                None => 30,
+            };
+            animate(&arena, second.saturating_sub(seconds)..second + 1, &path)?;
+        }
+
+        Ok(second)
     }
 
     let result = part1(TEST, 11, 7).await?;
     println!("Test Result 1 = {}", result);
     assert_eq!(12, result);
 
+    let arena = Arena::new(11, 7, parse(TEST2).await)?;
+    assert_eq!(vec![Position { x: 1, y: 3 }], arena.positions(5));
+
+    let arena = Arena::new(11, 7, parse(TEST).await)?;
+    assert_eq!(
+        vec![1, 3, 4, 1],
+        arena.count(MAX_SECONDS, &arena.middle_split())
+    );
+    assert_eq!(
+        vec![0, 3, 3, 4],
+        arena.count(MAX_SECONDS, &arena.split(3, 2))
+    );
+    let middle = Region { x: 3..8, y: 2..5 };
+    assert_eq!(vec![1], arena.count(MAX_SECONDS, &[middle]));
+    assert_eq!(77, arena.period());
+
+    // Empty quadrants do not zero the product
+    let corners = "p=0,0 v=0,0\np=1,1 v=0,0\np=10,6 v=0,0\n";
+    let arena_corners = Arena::new(11, 7, parse(corners).await)?;
+    assert_eq!(
+        vec![2, 0, 0, 1],
+        arena_corners.count(0, &arena_corners.middle_split())
+    );
+    assert_eq!(2, arena_corners.safety_factor(0));
+    assert_eq!(
+        arena.positions(MAX_SECONDS),
+        arena.positions(MAX_SECONDS + 77)
+    );
+
+    // The smallest t with t ≡ 1 (mod 11) and t ≡ 2 (mod 7)
+    assert_eq!(Some(23), chinese_remainder(1, 11, 2, 7));
+    assert_eq!(None, chinese_remainder(1, 4, 2, 6));
+
     let mut input_file = File::open(INPUT_FILE)?;
     let mut buffer = Vec::new();
     input_file.read_to_end(&mut buffer)?;
@@ -68,8 +123,8 @@ async fn main() -> Result<()> {
 
     println!("=== Part 2 ===");
 
-    part2(&input, MAX_WIDTH, MAX_HEIGHT).await?;
-    // 6398
+    let result = time_snippet!(part2(&input, MAX_WIDTH, MAX_HEIGHT).await?);
+    println!("Result 2 = {}", result);
 
     Ok(())
 }
@@ -125,146 +180,297 @@ async fn parse(input: &str) -> Vec<Bot> {
     bots
 }
 
-async fn look_for_easter_egg(bots: &mut [Bot], max_width: usize, max_height: usize) -> usize {
-    let mut i = 0;
-
-    loop {
-        i += 1;
-
-        for bot in bots.iter_mut() {
-            let mut pos_x = bot.position_x as isize;
-            let mut pos_y = bot.position_y as isize;
-            make_one_move(
-                bot,
-                &mut pos_x,
-                &mut pos_y,
-                max_width as isize,
-                max_height as isize,
-            );
-            bot.position_x = pos_x as usize;
-            bot.position_y = pos_y as usize;
-        }
+/// How a frame is scored along one axis: the tree packs robots together, so its frame has
+/// the lowest score.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Score {
+    Variance,
+    Entropy,
+}
 
-        let mut buffer: RgbImage = ImageBuffer::new(MAX_WIDTH as u32, MAX_HEIGHT as u32);
-        for x in 0..MAX_WIDTH {
-            for y in 0..MAX_HEIGHT {
-                let b = bots.iter().find(|b| b.position_x == x && b.position_y == y);
-                if b.is_some() {
-                    buffer.put_pixel(x as u32, y as u32, Rgb([255, 0, 0]));
-                } else {
-                    buffer.put_pixel(x as u32, y as u32, Rgb([255, 255, 255]));
+impl Score {
+    fn evaluate(&self, coordinates: &[usize], size: usize) -> f64 {
+        let n = coordinates.len() as f64;
+        match self {
+            Score::Variance => {
+                let mean = coordinates.iter().sum::<usize>() as f64 / n;
+                coordinates
+                    .iter()
+                    .map(|&c| (c as f64 - mean).powi(2))
+                    .sum::<f64>()
+                    / n
+            }
+            Score::Entropy => {
+                let mut histogram = vec![0usize; size];
+                for &c in coordinates {
+                    histogram[c] += 1;
                 }
+                histogram
+                    .iter()
+                    .filter(|&&count| count > 0)
+                    .map(|&count| {
+                        let p = count as f64 / n;
+                        -p * p.log2()
+                    })
+                    .sum()
             }
         }
+    }
+}
 
-        buffer
-            .save(format!("images/image{}.png", i))
-            .expect("Something is wrong");
+/// The x coordinates repeat every `width` seconds and the y ones every `height`, so the best
+/// scoring second of each axis is found separately and the two are combined.
+fn look_for_easter_egg(arena: &Arena, score: Score) -> Result<usize> {
+    if arena.bots.is_empty() {
+        bail!("There are no robots to look at");
+    }
+
+    let best = |size: usize, coordinate: fn(&Position) -> usize| {
+        (0..size)
+            .map(|second| {
+                let values = arena.positions(second).iter().map(coordinate).collect_vec();
+                (score.evaluate(&values, size), second)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, second)| second)
+            .unwrap_or(0)
+    };
+    let best_x = best(arena.width, |p| p.x);
+    let best_y = best(arena.height, |p| p.y);
+
+    chinese_remainder(best_x, arena.width, best_y, arena.height).ok_or_else(|| {
+        anyhow!(
+            "Width {} and height {} share a factor, the periods cannot be combined",
+            arena.width,
+            arena.height
+        )
+    })
+}
 
-        if i >= 10000 {
-            break;
+/// The smallest t with t = a (mod n) and t = b (mod m), when n and m are coprime.
+fn chinese_remainder(a: usize, n: usize, b: usize, m: usize) -> Option<usize> {
+    fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+        if b == 0 {
+            (a, 1, 0)
+        } else {
+            let (g, x, y) = extended_gcd(b, a % b);
+            (g, y, x - (a / b) * y)
         }
     }
 
-    i
+    let (n, m) = (n as i64, m as i64);
+    let (g, inverse, _) = extended_gcd(n, m);
+    if g != 1 {
+        return None;
+    }
+
+    // t = a + n * k, with n * k = b - a (mod m)
+    let k = ((b as i64 - a as i64) * inverse).rem_euclid(m);
+    Some((a as i64 + n * k).rem_euclid(n * m) as usize)
 }
 
-async fn execute_move(bots: Vec<Bot>, max_width: usize, max_height: usize) -> usize {
-    let mut set = JoinSet::new();
-    for bot in bots {
-        set.spawn(move_bot(bot, max_width as isize, max_height as isize));
+/// Size of the biggest group of robots touching each other horizontally or vertically.
+fn largest_cluster(positions: &[Position]) -> usize {
+    let mut remaining: HashSet<(usize, usize)> = positions.iter().map(|p| (p.x, p.y)).collect();
+    let mut largest = 0;
+
+    while let Some(&start) = remaining.iter().next() {
+        remaining.remove(&start);
+        let mut queue = vec![start];
+        let mut size = 0;
+
+        while let Some((x, y)) = queue.pop() {
+            size += 1;
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for neighbour in neighbours {
+                if remaining.remove(&neighbour) {
+                    queue.push(neighbour);
+                }
+            }
+        }
+
+        largest = largest.max(size);
     }
-    let output = set.join_all().await;
-
-    let mut total = 1;
-    for q in output
-        .iter()
-        .filter_map(|p| get_quadrant(max_width, max_height, p))
-        .sorted()
-        .chunk_by(|&q| q)
-        .into_iter()
-    {
-        total *= q.1.count();
+
+    largest
+}
+
+fn save_frame(positions: &[Position], width: usize, height: usize, path: &str) -> Result<()> {
+    let mut buffer: RgbImage =
+        ImageBuffer::from_pixel(width as u32, height as u32, Rgb([255, 255, 255]));
+    for position in positions.iter() {
+        buffer.put_pixel(position.x as u32, position.y as u32, Rgb([255, 0, 0]));
     }
 
-    total
+    buffer.save(path)?;
+    Ok(())
 }
 
-fn get_quadrant(max_width: usize, max_height: usize, position: &Position) -> Option<usize> {
-    let split_x;
-    let split_y;
-    let split_x2;
-    let split_y2;
-
-    if max_width % 2 == 0 {
-        split_x = max_width / 2;
-        split_x2 = split_x;
-    } else {
-        split_x = (max_width - 1) / 2;
-        split_x2 = split_x + 1;
-    };
+/// Writes a GIF or APNG of the arena over the given seconds.
+fn animate(arena: &Arena, seconds: Range<usize>, path: &str) -> Result<()> {
+    let mut animation = Animation::new(vec![[255, 255, 255], [255, 0, 0]], 100);
+    animation.set_scale(4);
 
-    if max_height % 2 == 0 {
-        split_y = max_height / 2;
-        split_y2 = split_y;
-    } else {
-        split_y = (max_height - 1) / 2;
-        split_y2 = split_y + 1;
-    };
+    for second in seconds {
+        let mut frame = Frame::new(arena.width, arena.height);
+        for position in arena.positions(second) {
+            frame.set(position.x, position.y, 1)?;
+        }
+        animation.push(frame)?;
+    }
+
+    animation.save(path)
+}
+
+/// Cells with `x` and `y` in the given ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Region {
+    x: Range<usize>,
+    y: Range<usize>,
+}
+
+impl Region {
+    fn contains(&self, position: &Position) -> bool {
+        self.x.contains(&position.x) && self.y.contains(&position.y)
+    }
+}
+
+/// Robots on a wrapping `width` x `height` floor.
+struct Arena {
+    width: usize,
+    height: usize,
+    bots: Vec<Bot>,
+}
 
-    if position.x < split_x {
-        if position.y < split_y {
-            return Some(1);
-        } else if position.y >= split_y2 {
-            return Some(3);
+impl Arena {
+    fn new(width: usize, height: usize, bots: Vec<Bot>) -> Result<Self> {
+        if width == 0 || height == 0 {
+            bail!("The arena cannot be {}x{}", width, height);
         }
-    } else if position.x >= split_x2 {
-        if position.y < split_y {
-            return Some(2);
-        } else if position.y >= split_y2 {
-            return Some(4);
+        if let Some(bot) = bots
+            .iter()
+            .find(|b| b.position_x >= width || b.position_y >= height)
+        {
+            bail!(
+                "Robot at {},{} is outside of the {}x{} arena",
+                bot.position_x,
+                bot.position_y,
+                width,
+                height
+            );
         }
+
+        Ok(Self {
+            width,
+            height,
+            bots,
+        })
     }
 
-    None
-}
+    fn position(&self, bot: &Bot, second: usize) -> Position {
+        let walk = |start: usize, velocity: isize, size: usize| {
+            let size = size as i128;
+            let distance = (velocity as i128 * (second as i128)).rem_euclid(size);
+            ((start as i128 + distance) % size) as usize
+        };
 
-async fn move_bot(bot: Bot, max_width: isize, max_height: isize) -> Position {
-    let mut pos_x = bot.position_x as isize;
-    let mut pos_y = bot.position_y as isize;
+        Position {
+            x: walk(bot.position_x, bot.velocity_x, self.width),
+            y: walk(bot.position_y, bot.velocity_y, self.height),
+        }
+    }
 
-    for _ in 0..MAX_SECONDS {
-        make_one_move(&bot, &mut pos_x, &mut pos_y, max_width, max_height);
+    fn positions(&self, second: usize) -> Vec<Position> {
+        self.bots
+            .iter()
+            .map(|bot| self.position(bot, second))
+            .collect_vec()
     }
 
-    Position {
-        x: pos_x as usize,
-        y: pos_y as usize,
+    /// The four quadrants around column `x` and row `y`; robots on those lines are in none.
+    fn split(&self, x: usize, y: usize) -> [Region; 4] {
+        let left = 0..x.min(self.width);
+        let right = (x + 1).min(self.width)..self.width;
+        let top = 0..y.min(self.height);
+        let bottom = (y + 1).min(self.height)..self.height;
+
+        [
+            Region {
+                x: left.clone(),
+                y: top.clone(),
+            },
+            Region {
+                x: right.clone(),
+                y: top,
+            },
+            Region {
+                x: left,
+                y: bottom.clone(),
+            },
+            Region {
+                x: right,
+                y: bottom,
+            },
+        ]
+    }
+
+    /// The puzzle quadrants: around the middle lines, or between the halves of an even size.
+    fn middle_split(&self) -> [Region; 4] {
+        let mut regions = self.split(self.width / 2, self.height / 2);
+        if self.width.is_multiple_of(2) {
+            regions[1].x.start -= 1;
+            regions[3].x.start -= 1;
+        }
+        if self.height.is_multiple_of(2) {
+            regions[2].y.start -= 1;
+            regions[3].y.start -= 1;
+        }
+        regions
     }
-}
 
-fn make_one_move(
-    bot: &Bot,
-    pos_x: &mut isize,
-    pos_y: &mut isize,
-    max_width: isize,
-    max_height: isize,
-) {
-    let potential_x = *pos_x + bot.velocity_x;
-    if potential_x < 0 {
-        *pos_x = max_width - potential_x.abs();
-    } else if potential_x > (max_width - 1) {
-        *pos_x = potential_x - max_width;
-    } else {
-        *pos_x = potential_x;
+    /// Robots in each region after `second` seconds.
+    fn count(&self, second: usize, regions: &[Region]) -> Vec<usize> {
+        let positions = self.positions(second);
+        regions
+            .iter()
+            .map(|region| positions.iter().filter(|p| region.contains(p)).count())
+            .collect_vec()
     }
 
-    let potential_y = *pos_y + bot.velocity_y;
-    if potential_y < 0 {
-        *pos_y = max_height - potential_y.abs();
-    } else if potential_y > (max_height - 1) {
-        *pos_y = potential_y - max_height;
-    } else {
-        *pos_y = potential_y;
+    /// Product of the robots in each quadrant, leaving out the empty ones.
+    fn safety_factor(&self, second: usize) -> usize {
+        self.count(second, &self.middle_split())
+            .iter()
+            .filter(|&&count| count > 0)
+            .product()
+    }
+
+    /// Seconds after which every robot is back where it started.
+    fn period(&self) -> usize {
+        fn gcd(a: usize, b: usize) -> usize {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+
+        let cycle = |velocity: isize, size: usize| {
+            size / gcd(velocity.rem_euclid(size as isize) as usize, size)
+        };
+
+        self.bots.iter().fold(1, |period, bot| {
+            [
+                cycle(bot.velocity_x, self.width),
+                cycle(bot.velocity_y, self.height),
+            ]
+            .iter()
+            .fold(period, |p, &c| p / gcd(p, c) * c)
+        })
     }
 }
@@ -0,0 +1,676 @@
+use adv_code_2024::animation::{every_option, Animation, Color, Frame};
+use adv_code_2024::grid::Direction;
+use adv_code_2024::*;
+use anyhow::*;
+use code_timing_macros::time_snippet;
+use const_format::concatcp;
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+
+const DAY: &str = "15";
+const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
+
+const TEST: &str = "\
+########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########
+
+<^^>>>vv<v>>v<<
+";
+
+const TEST2: &str = "\
+##########
+#..O..O.O#
+#......O.#
+#.OO..O.O#
+#..O@..O.#
+#O#..O...#
+#O..O..O.#
+#.OO.O.OO#
+#....O...#
+##########
+
+<vv>^<v^>v>^vv^v>v<>v^v<v<^vv<<<^><<><>>v<vvv<>^v^>^<<<><<v<<<v^vv^v>^
+vvv<<^>^v^^><<>>><>^<<><^vv^^<>vvv<>><^^v>^>vv<>v<<<<v<^v>^<^^>>>^<v<v
+><>vv>v^v^<>><>>>><^^>vv>v<^^^>>v^v^<^^>v^^>v^<^v>v<>>v^v^<v>v^^<^^vv<
+<<v<^>>^^^^>>>v^<>vvv^><v<<<>^^^vv^<vvv>^>v<^^^^v<>^>vvvv><>>v^<<^^^^^
+^><^><>>><>^^<<^^v>>><^<v>^<vv>>v>>>^v><>^v><<<<v>>v<v<v>vvv>^<><<>^><
+^>><>^v<><^vvv<^^<><v<<<<<><^v<<<><<<^^<v<^^^><^>>^<v^><<<^>>^v<v^v<v^
+>^>>^v>vv>^<<^v<>><<><<v<<v><>v<^vv<<<>^^v^>^^>>><<^v>>v^v><^^>>^<>vv^
+<><^^>^^^<><vvvvv^v<v<<>^v<v>v<<^><<><<><<<^^<<<^<<>><<><^^^>^^<>^>v<>
+^^>vv<^v^v<vv>^<><v<^v>^^^>>>^^vvv^>vvv<>>>^<^>>>>>^<<^v>^vvv<>^<><<v>
+v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^
+";
+
+const TEST3: &str = "\
+#######
+#...#.#
+#.....#
+#..OO@#
+#..O..#
+#.....#
+#######
+
+<vv<<^^<<^^
+";
+
+// Boxes of any shape, declared in a legend before the map
+const TEST4: &str = "\
+A: box
+B: box
+
+#########
+#.......#
+#..A....#
+#..A.BB.#
+#.@O..B.#
+#.......#
+#########
+
+>>>>
+";
+
+const TEST5: &str = "\
+A: box
+
+#####
+#...#
+#.A.#
+#.A.#
+#.@.#
+#####
+
+^^
+";
+
+// Frames kept in an animation whatever the number of moves
+const ANIMATION_FRAMES: usize = 500;
+// Floor, wall, box, robot
+const PALETTE: [Color; 4] = [[20, 20, 30], [120, 120, 120], [200, 150, 50], [230, 40, 40]];
+
+fn main() -> Result<()> {
+    start_day(DAY);
+
+    println!("=== Part 1 ===");
+
+    fn part1(input: &str) -> Result<usize> {
+        let (legend, map, instructions) = parse(input)?;
+        let mut map = Map::new(map, &legend)?;
+        map.apply_all(&instructions)?;
+        Ok(map.gps())
+    }
+
+    fn part2(input: &str) -> Result<usize> {
+        let (legend, map, instructions) = parse(input)?;
+        let mut map = Map::new_wide(map, &legend)?;
+        map.apply_all(&instructions)?;
+        Ok(map.gps())
+    }
+
+    let result = part1(TEST)?;
+    println!("Test Result 1 = {}", result);
+    assert_eq!(2028, result);
+
+    let result = part1(TEST2)?;
+    println!("Test Result 1 = {}", result);
+    assert_eq!(10092, result);
+
+    // Every step of the small example, as described by the puzzle
+    let (legend, map, instructions) = parse(TEST)?;
+    let mut map = Map::new(map, &legend)?;
+    let steps = map.steps(&instructions).collect::<Result<Vec<Step>>>()?;
+    assert_eq!(15, steps.len());
+    assert!(!steps[0].moved);
+    assert!(steps[1].moved && !steps[2].moved);
+    assert_eq!(1, steps[3].boxes_pushed);
+    assert_eq!(2, steps[4].boxes_pushed);
+    assert!(!steps[5].moved);
+
+    let error = parse("#@.#\n\n<>x\n").unwrap_err();
+    println!("{}", error);
+    assert_eq!("Unknown move 'x' at line 1, column 3", error.to_string());
+
+    let (legend, map, instructions) = parse(TEST4)?;
+    let mut map = Map::new(map, &legend)?;
+    let steps = map.steps(&instructions).collect::<Result<Vec<Step>>>()?;
+    assert_eq!(
+        vec![1, 1, 2, 0],
+        steps.iter().map(|s| s.boxes_pushed).collect_vec()
+    );
+    let chain = map.push_chain(Direction::Right);
+    println!("{}", map.describe(&chain));
+    assert_eq!(
+        "blocked by wall at (8,3) via box chain (6,4) -> (6,3)",
+        map.describe(&chain)
+    );
+    assert_eq!(
+        "\
+#########
+#.......#
+#..A....#
+#..A..BB#
+#....@OB#
+#.......#
+#########
+",
+        map.to_string()
+    );
+    assert_eq!(915, map.gps());
+
+    assert_eq!(102, part1(TEST5)?);
+    let (legend, map, instructions) = parse(TEST5)?;
+    let mut map = Map::new(map, &legend)?;
+    assert_eq!(
+        "pushed 1 box: (2,2)",
+        map.describe(&map.push_chain(Direction::Up))
+    );
+    map.apply_all(&instructions)?;
+    assert_eq!(
+        "blocked by wall at (2,0) via box chain (2,1)",
+        map.describe(&map.push_chain(Direction::Up))
+    );
+
+    println!("=== Part 2 ===");
+
+    let result = part2(TEST2)?;
+    println!("Test Result 2 = {}", result);
+    assert_eq!(9021, result);
+
+    // A vertical box becomes a square in the wide warehouse
+    assert_eq!(104, part2(TEST5)?);
+
+    let (legend, map, instructions) = parse(TEST3)?;
+    let mut map = Map::new_wide(map, &legend)?;
+    map.apply_all(&instructions)?;
+    print!("{}", map);
+    assert_eq!(
+        "\
+##############
+##...[].##..##
+##...@.[]...##
+##....[]....##
+##..........##
+##..........##
+##############
+",
+        map.to_string()
+    );
+
+    let mut input_file = File::open(INPUT_FILE)?;
+    let mut buffer = Vec::new();
+    input_file.read_to_end(&mut buffer)?;
+    let input = String::from_utf8_lossy(&buffer);
+
+    let result = time_snippet!(part1(&input)?);
+    println!("Result 1 = {}", result);
+
+    let result = time_snippet!(part2(&input)?);
+    println!("Result 2 = {}", result);
+
+    if let Some(frame) = cli_option("frame") {
+        let frame: usize = frame.parse()?;
+        let (legend, map, instructions) = parse(&input)?;
+        let mut map = Map::new_wide(map, &legend)?;
+        let mut steps = map.steps(&instructions);
+        for _ in 0..frame {
+            steps.next().transpose()?;
+        }
+        print!("{}", steps.current());
+    }
+
+    if cli_flag("explain") {
+        let (legend, map, instructions) = parse(&input)?;
+        let mut map = Map::new_wide(map, &legend)?;
+        for (i, &direction) in instructions.iter().enumerate() {
+            let chain = map.push_chain(direction);
+            println!(
+                "{:>6} {} {}",
+                i + 1,
+                direction.arrow(),
+                map.describe(&chain)
+            );
+            map.apply(direction);
+        }
+    }
+
+    if let Some(path) = cli_option("animate") {
+        let (legend, map, instructions) = parse(&input)?;
+        let mut map = Map::new_wide(map, &legend)?;
+        let every = every_option(instructions.len() / ANIMATION_FRAMES)?;
+
+        let mut animation = Animation::new(PALETTE.to_vec(), 40);
+        animation.set_scale(4);
+        animation.push(map.frame())?;
+        let mut steps = map.steps(&instructions);
+        let mut moves = 0;
+        while let Some(step) = steps.next() {
+            step?;
+            moves += 1;
+            if moves % every == 0 || moves == instructions.len() {
+                animation.push(steps.current().frame())?;
+            }
+        }
+        animation.save(path)?;
+    }
+
+    Ok(())
+}
+
+/// Splits the puzzle into the legend, the map and the robot moves. The legend is optional.
+fn parse(input: &str) -> Result<(Legend, &str, Vec<Direction>)> {
+    fn split_block(input: &str) -> Option<(&str, &str)> {
+        input
+            .split_once("\n\n")
+            .or_else(|| input.split_once("\r\n\r\n"))
+    }
+
+    let (first, rest) = split_block(input)
+        .ok_or_else(|| anyhow!("Missing the empty line between the map and the moves"))?;
+    let (legend, (map, moves)) = if first.contains(':') {
+        let blocks = split_block(rest)
+            .ok_or_else(|| anyhow!("Missing the empty line between the map and the moves"))?;
+        (Legend::parse(first)?, blocks)
+    } else {
+        (Legend::default(), (first, rest))
+    };
+
+    let mut instructions = Vec::new();
+    for (line, text) in moves.lines().enumerate() {
+        for (column, c) in text.trim_end().chars().enumerate() {
+            let direction = Direction::from_arrow(c).ok_or_else(|| {
+                anyhow!(
+                    "Unknown move '{}' at line {}, column {}",
+                    c,
+                    line + 1,
+                    column + 1
+                )
+            })?;
+            instructions.push(direction);
+        }
+    }
+
+    Ok((legend, map, instructions))
+}
+
+/// Letters drawing boxes of any shape, on top of the puzzle's `O` and `[]`.
+/// Touching cells with the same letter are one box, so neighbours need different letters.
+#[derive(Debug, Clone, Default)]
+struct Legend {
+    boxes: HashSet<char>,
+}
+
+impl Legend {
+    /// One `A: box` line per letter.
+    fn parse(block: &str) -> Result<Legend> {
+        let mut boxes = HashSet::new();
+        for line in block.lines().filter(|l| !l.trim().is_empty()) {
+            let (letter, kind) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Legend line '{}' is not like 'A: box'", line))?;
+            let mut letters = letter.trim().chars();
+            let (Some(letter), None) = (letters.next(), letters.next()) else {
+                bail!("Legend line '{}' should name a single character", line);
+            };
+            if "#.@O[]".contains(letter) || Direction::from_arrow(letter).is_some() {
+                bail!("'{}' is already used by the puzzle", letter);
+            }
+            if kind.trim() != "box" {
+                bail!("Unknown kind '{}' for '{}'", kind.trim(), letter);
+            }
+            boxes.insert(letter);
+        }
+        Ok(Legend { boxes })
+    }
+}
+
+/// What happened to the robot on one move.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Step {
+    direction: Direction,
+    moved: bool,
+    boxes_pushed: usize,
+}
+
+/// A box and the tiles it is drawn with, in reading order.
+#[derive(Debug, Clone)]
+struct Package {
+    cells: Vec<(usize, usize)>,
+    tiles: Vec<char>,
+}
+
+/// The boxes a move would push, by index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PushChain {
+    Free(Vec<usize>),
+    /// The wall, if still on the map, and the boxes leading the robot to it.
+    Blocked {
+        wall: Option<(usize, usize)>,
+        chain: Vec<usize>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Map {
+    walls: Vec<Vec<bool>>,
+    boxes: Vec<Package>,
+    // Index of the box covering each cell
+    occupied: Vec<Vec<Option<usize>>>,
+    // Row and column of the robot
+    robot: (usize, usize),
+}
+
+impl Map {
+    fn new(input: &str, legend: &Legend) -> Result<Self> {
+        Self::build(input, legend, |c| vec![c])
+    }
+
+    /// The map of the second warehouse, where everything but the robot is twice as wide.
+    fn new_wide(input: &str, legend: &Legend) -> Result<Self> {
+        Self::build(input, legend, |c| match c {
+            'O' => vec!['[', ']'],
+            '@' => vec!['@', '.'],
+            c => vec![c, c],
+        })
+    }
+
+    fn build(input: &str, legend: &Legend, widen: impl Fn(char) -> Vec<char>) -> Result<Self> {
+        let mut grid = Vec::new();
+        for (r, line) in input.lines().enumerate() {
+            let mut row = Vec::new();
+            for c in line.trim_end().chars() {
+                if !"#.O@[]".contains(c) && !legend.boxes.contains(&c) {
+                    bail!("Unknown tile '{}' on row {}", c, r + 1);
+                }
+                row.extend(widen(c));
+            }
+            grid.push(row);
+        }
+
+        let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut map = Map {
+            walls: vec![vec![false; width]; grid.len()],
+            boxes: Vec::new(),
+            occupied: vec![vec![None; width]; grid.len()],
+            robot: (0, 0),
+        };
+        let mut robot = None;
+
+        for (r, row) in grid.iter().enumerate() {
+            for (c, &tile) in row.iter().enumerate() {
+                match tile {
+                    '#' => map.walls[r][c] = true,
+                    '@' if robot.is_some() => bail!("There is more than one robot"),
+                    '@' => robot = Some((r, c)),
+                    '.' => {}
+                    _ if map.occupied[r][c].is_some() => {}
+                    'O' => map.add_box(vec![(r, c)], &grid),
+                    '[' if row.get(c + 1) == Some(&']') => {
+                        map.add_box(vec![(r, c), (r, c + 1)], &grid)
+                    }
+                    '[' | ']' => {
+                        bail!("Half box '{}' at row {}, column {} has no pair", tile, r, c)
+                    }
+                    letter => {
+                        // Flood fill the touching cells drawn with the same letter
+                        let mut cells = vec![(r, c)];
+                        let mut seen = HashSet::from([(r, c)]);
+                        let mut queue = VecDeque::from([(r, c)]);
+                        while let Some(cell) = queue.pop_front() {
+                            for next in Direction::ALL.iter().filter_map(|d| d.step(cell)) {
+                                let same = grid.get(next.0).and_then(|row| row.get(next.1))
+                                    == Some(&letter);
+                                if same && seen.insert(next) {
+                                    cells.push(next);
+                                    queue.push_back(next);
+                                }
+                            }
+                        }
+                        cells.sort();
+                        map.add_box(cells, &grid);
+                    }
+                }
+            }
+        }
+
+        map.robot = robot.ok_or_else(|| anyhow!("There is no robot on the map"))?;
+        map.check()?;
+        Ok(map)
+    }
+
+    fn add_box(&mut self, cells: Vec<(usize, usize)>, grid: &[Vec<char>]) {
+        let index = self.boxes.len();
+        for &(r, c) in cells.iter() {
+            self.occupied[r][c] = Some(index);
+        }
+        let tiles = cells.iter().map(|&(r, c)| grid[r][c]).collect();
+        self.boxes.push(Package { cells, tiles });
+    }
+
+    /// Walls surround the map, whatever its input looks like.
+    fn is_wall(&self, (r, c): (usize, usize)) -> bool {
+        self.walls
+            .get(r)
+            .and_then(|row| row.get(c))
+            .copied()
+            .unwrap_or(true)
+    }
+
+    fn apply_all(&mut self, instructions: &[Direction]) -> Result<()> {
+        for step in self.steps(instructions) {
+            step?;
+        }
+        Ok(())
+    }
+
+    /// Applies the moves one at a time, checking the map after each of them.
+    fn steps<'a>(&'a mut self, instructions: &'a [Direction]) -> Steps<'a> {
+        Steps {
+            map: self,
+            instructions: instructions.iter(),
+        }
+    }
+
+    /// Every box the robot would push, or the chain of boxes that ends against a wall.
+    fn push_chain(&self, direction: Direction) -> PushChain {
+        // Which box, or the robot for None, pushes each box
+        let mut pushed_by: HashMap<usize, Option<usize>> = HashMap::new();
+        let mut order = Vec::new();
+        let mut queue: VecDeque<Option<usize>> = VecDeque::from([None]);
+
+        while let Some(pusher) = queue.pop_front() {
+            let cells = match pusher {
+                None => vec![self.robot],
+                Some(index) => self.boxes[index].cells.clone(),
+            };
+
+            for cell in cells {
+                let next = direction.step(cell);
+                if next.is_none_or(|next| self.is_wall(next)) {
+                    let mut chain = Vec::new();
+                    let mut current = pusher;
+                    while let Some(index) = current {
+                        chain.push(index);
+                        current = pushed_by[&index];
+                    }
+                    chain.reverse();
+                    return PushChain::Blocked { wall: next, chain };
+                }
+
+                let (r, c) = next.unwrap();
+                if let Some(index) = self.occupied[r][c] {
+                    if Some(index) != pusher && !pushed_by.contains_key(&index) {
+                        pushed_by.insert(index, pusher);
+                        order.push(index);
+                        queue.push_back(Some(index));
+                    }
+                }
+            }
+        }
+
+        PushChain::Free(order)
+    }
+
+    fn apply(&mut self, direction: Direction) -> Step {
+        let PushChain::Free(chain) = self.push_chain(direction) else {
+            return Step {
+                direction,
+                moved: false,
+                boxes_pushed: 0,
+            };
+        };
+
+        for &index in chain.iter() {
+            for &(r, c) in self.boxes[index].cells.iter() {
+                self.occupied[r][c] = None;
+            }
+        }
+        for &index in chain.iter() {
+            for cell in self.boxes[index].cells.iter_mut() {
+                *cell = direction.step(*cell).unwrap();
+                self.occupied[cell.0][cell.1] = Some(index);
+            }
+        }
+        self.robot = direction.step(self.robot).unwrap();
+
+        Step {
+            direction,
+            moved: true,
+            boxes_pushed: chain.len(),
+        }
+    }
+
+    /// The push chain in words, with boxes named by the (x,y) of their first cell.
+    fn describe(&self, chain: &PushChain) -> String {
+        let name = |index: &usize| {
+            let (r, c) = self.boxes[*index].cells[0];
+            format!("({},{})", c, r)
+        };
+
+        match chain {
+            PushChain::Free(boxes) if boxes.is_empty() => "moved".to_string(),
+            PushChain::Free(boxes) => format!(
+                "pushed {} box{}: {}",
+                boxes.len(),
+                if boxes.len() == 1 { "" } else { "es" },
+                boxes.iter().map(name).join(", ")
+            ),
+            PushChain::Blocked { wall, chain } => {
+                let mut text = match wall {
+                    Some((r, c)) => format!("blocked by wall at ({},{})", c, r),
+                    None => "blocked by the edge of the map".to_string(),
+                };
+                if !chain.is_empty() {
+                    text += &format!(" via box chain {}", chain.iter().map(name).join(" -> "));
+                }
+                text
+            }
+        }
+    }
+
+    /// Makes sure the robot is on free floor and every box still covers exactly its own cells.
+    fn check(&self) -> Result<()> {
+        let (r, c) = self.robot;
+        if self.is_wall(self.robot) || self.occupied[r][c].is_some() {
+            bail!(
+                "The robot at row {}, column {} overlaps a wall or a box",
+                r,
+                c
+            );
+        }
+
+        let mut covered = 0;
+        for (index, package) in self.boxes.iter().enumerate() {
+            for &(r, c) in package.cells.iter() {
+                if self.is_wall((r, c)) || self.occupied[r][c] != Some(index) {
+                    bail!("Box {} lost its cell at row {}, column {}", index, r, c);
+                }
+            }
+            covered += package.cells.len();
+        }
+        if self.occupied.iter().flatten().flatten().count() != covered {
+            bail!("The map holds cells of boxes that moved away");
+        }
+
+        Ok(())
+    }
+
+    /// Boxes measured from their top and left-most edges, as the puzzle does for `[]`.
+    fn gps(&self) -> usize {
+        self.boxes
+            .iter()
+            .map(|package| {
+                let top = package.cells.iter().map(|&(r, _)| r).min().unwrap();
+                let left = package.cells.iter().map(|&(_, c)| c).min().unwrap();
+                top * 100 + left
+            })
+            .sum()
+    }
+
+    fn tiles(&self) -> Vec<Vec<char>> {
+        let mut tiles = self
+            .walls
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&wall| if wall { '#' } else { '.' })
+                    .collect_vec()
+            })
+            .collect_vec();
+        for package in self.boxes.iter() {
+            for (&(r, c), &tile) in package.cells.iter().zip(package.tiles.iter()) {
+                tiles[r][c] = tile;
+            }
+        }
+        tiles[self.robot.0][self.robot.1] = '@';
+        tiles
+    }
+
+    fn frame(&self) -> Frame {
+        Frame::from_tiles(&self.tiles(), |tile| match tile {
+            '.' => 0,
+            '#' => 1,
+            '@' => 3,
+            _ => 2,
+        })
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for row in self.tiles() {
+            writeln!(f, "{}", row.iter().collect::<String>())?;
+        }
+        fmt::Result::Ok(())
+    }
+}
+
+struct Steps<'a> {
+    map: &'a mut Map,
+    instructions: std::slice::Iter<'a, Direction>,
+}
+
+impl Steps<'_> {
+    /// The map as left by the last step.
+    fn current(&self) -> &Map {
+        self.map
+    }
+}
+
+impl Iterator for Steps<'_> {
+    type Item = Result<Step>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &direction = self.instructions.next()?;
+        let step = self.map.apply(direction);
+        Some(self.map.check().map(|_| step))
+    }
+}
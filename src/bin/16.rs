@@ -0,0 +1,453 @@
+use adv_code_2024::grid::Direction;
+use adv_code_2024::*;
+use anyhow::*;
+use code_timing_macros::time_snippet;
+use const_format::concatcp;
+use itertools::Itertools;
+use num_bigint::BigUint;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::Read;
+
+const DAY: &str = "16";
+const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
+
+const TEST: &str = "\
+###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############
+";
+
+const TEST2: &str = "\
+#################
+#...#...#...#..E#
+#.#.#.#.#.#.#.#.#
+#.#.#.#...#...#.#
+#.#.#.#.###.#.#.#
+#...#.#.#.....#.#
+#.#.#.#.#.#####.#
+#.#...#.#.#.....#
+#.#.#####.#.###.#
+#.#.#.......#...#
+#.#.###.#####.###
+#.#.#...#.....#.#
+#.#.#.#####.###.#
+#.#.#.........#.#
+#.#.#.#########.#
+#S#.............#
+#################
+";
+
+const TEST_OVERLAY: &str = "\
+###############
+#.......#....O#
+#.#.###.#.###O#
+#.....#.#...#O#
+#.###.#####.#O#
+#.#.#.......#O#
+#.#.#####.###O#
+#..OOOOOOOOO#O#
+###O#O#####O#O#
+#OOO#O....#O#O#
+#O#O#O###.#O#O#
+#OOOOO#...#O#O#
+#O###.#.#.#O#O#
+#O..#.....#OOO#
+###############
+";
+
+const TEST4: &str = "\
+#####
+#...#
+#S#E#
+#...#
+#####
+";
+
+const TEST3: &str = "\
+######
+#...E#
+#S####
+######
+";
+
+const TEST5: &str = "\
+#####
+#E.S#
+#####
+";
+
+fn main() -> Result<()> {
+    start_day(DAY);
+
+    println!("=== Part 1 ===");
+
+    fn part1(input: &str) -> Result<u64> {
+        let maze = Maze::parse(input)?;
+        Ok(maze.solve(Costs::default(), Direction::Right)?.score)
+    }
+
+    fn part2(input: &str) -> Result<usize> {
+        let maze = Maze::parse(input)?;
+        Ok(maze.solve(Costs::default(), Direction::Right)?.tiles.len())
+    }
+
+    let result = part1(TEST)?;
+    println!("Test Result 1 = {}", result);
+    assert_eq!(7036, result);
+
+    let result = part1(TEST2)?;
+    println!("Test Result 1 = {}", result);
+    assert_eq!(11048, result);
+
+    // Costs and the starting orientation are up to the caller
+    let maze = Maze::parse(TEST3)?;
+    assert_eq!(2004, maze.solve(Costs::default(), Direction::Right)?.score);
+    assert_eq!(1004, maze.solve(Costs::default(), Direction::Up)?.score);
+    let costs = Costs {
+        forward: 10,
+        turn: 1,
+    };
+    let solution = maze.solve(costs, Direction::Right)?;
+    assert_eq!(42, solution.score);
+    assert_eq!(5, solution.tiles.len());
+
+    println!("=== Part 2 ===");
+
+    let result = part2(TEST)?;
+    println!("Test Result 2 = {}", result);
+    assert_eq!(45, result);
+
+    let result = part2(TEST2)?;
+    println!("Test Result 2 = {}", result);
+    assert_eq!(64, result);
+
+    let maze = Maze::parse(TEST)?;
+    let solution = maze.solve(Costs::default(), Direction::Right)?;
+    assert_eq!(TEST_OVERLAY, maze.render(&solution, None));
+    assert_eq!(BigUint::from(3u8), solution.path_count());
+    println!("{}", maze.render(&solution, Some(&solution.path())));
+
+    let maze = Maze::parse(TEST2)?;
+    let solution = maze.solve(Costs::default(), Direction::Right)?;
+    assert_eq!(BigUint::from(2u8), solution.path_count());
+
+    // Around the pillar either way
+    let maze = Maze::parse(TEST4)?;
+    let solution = maze.solve(Costs::default(), Direction::Right)?;
+    assert_eq!(3004, solution.score);
+    assert_eq!(BigUint::from(2u8), solution.path_count());
+    assert_eq!(8, solution.tiles.len());
+
+    // Turning around to the left or to the right still walks the same tiles
+    let maze = Maze::parse(TEST5)?;
+    let solution = maze.solve(Costs::default(), Direction::Right)?;
+    assert_eq!(2002, solution.score);
+    assert_eq!(BigUint::from(1u8), solution.path_count());
+
+    let mut input_file = File::open(cli_option("input").unwrap_or(INPUT_FILE.to_string()))?;
+    let mut buffer = Vec::new();
+    input_file.read_to_end(&mut buffer)?;
+    let input = String::from_utf8_lossy(&buffer);
+
+    let result = time_snippet!(part1(&input)?);
+    println!("Result 1 = {}", result);
+
+    let result = time_snippet!(part2(&input)?);
+    println!("Result 2 = {}", result);
+
+    let maze = Maze::parse(&input)?;
+    let solution = maze.solve(Costs::default(), Direction::Right)?;
+    println!("Best paths = {}", solution.path_count());
+    if cli_flag("show") {
+        print!("{}", maze.render(&solution, Some(&solution.path())));
+    }
+
+    // Both answers come out of a single search, with any costs and starting orientation
+    let mut costs = Costs::default();
+    if let Some(forward) = cli_option("forward-cost") {
+        costs.forward = forward.parse()?;
+    }
+    if let Some(turn) = cli_option("turn-cost") {
+        costs.turn = turn.parse()?;
+    }
+    let facing = match cli_option("facing") {
+        Some(facing) => parse_facing(&facing)?,
+        None => Direction::Right,
+    };
+    if costs != Costs::default() || facing != Direction::Right {
+        let solution = Maze::parse(&input)?.solve(costs, facing)?;
+        println!("Score = {}", solution.score);
+        println!("Tiles = {}", solution.tiles.len());
+    }
+
+    Ok(())
+}
+
+/// Reads an arrow or a compass point.
+fn parse_facing(text: &str) -> Result<Direction> {
+    let direction = match text.to_lowercase().as_str() {
+        "n" | "north" | "^" => Direction::Up,
+        "e" | "east" | ">" => Direction::Right,
+        "s" | "south" | "v" => Direction::Down,
+        "w" | "west" | "<" => Direction::Left,
+        _ => bail!("Unknown orientation '{}', use N, E, S, W or an arrow", text),
+    };
+    Ok(direction)
+}
+
+/// What the reindeer pays to step forward and to turn a quarter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Costs {
+    forward: u64,
+    turn: u64,
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Costs {
+            forward: 1,
+            turn: 1000,
+        }
+    }
+}
+
+type Position = (usize, usize);
+type State = (Position, Direction);
+
+/// The lowest score and every tile on at least one path with that score.
+#[derive(Debug, Clone)]
+struct Solution {
+    score: u64,
+    tiles: HashSet<Position>,
+    start: State,
+    // Best states at the end tile, one per orientation they arrive with
+    ends: Vec<State>,
+    // Equally cheap ways into each state on a best path, and the score there
+    came_from: HashMap<State, Vec<State>>,
+    scores: HashMap<State, u64>,
+}
+
+#[derive(Debug, Clone)]
+struct Maze {
+    walls: Vec<Vec<bool>>,
+    start: Position,
+    end: Position,
+}
+
+impl Maze {
+    fn parse(input: &str) -> Result<Maze> {
+        let mut walls = Vec::new();
+        let mut start = None;
+        let mut end = None;
+
+        for (r, line) in input.trim().lines().enumerate() {
+            let mut row = Vec::new();
+            for (c, tile) in line.trim_end().chars().enumerate() {
+                match tile {
+                    'S' => start = Some((r, c)),
+                    'E' => end = Some((r, c)),
+                    '#' | '.' => {}
+                    _ => bail!("Unknown tile '{}' at row {}, column {}", tile, r, c),
+                }
+                row.push(tile == '#');
+            }
+            walls.push(row);
+        }
+
+        Ok(Maze {
+            walls,
+            start: start.ok_or_else(|| anyhow!("No start tile 'S' in the maze"))?,
+            end: end.ok_or_else(|| anyhow!("No end tile 'E' in the maze"))?,
+        })
+    }
+
+    fn is_open(&self, (r, c): Position) -> bool {
+        self.walls
+            .get(r)
+            .and_then(|row| row.get(c))
+            .is_some_and(|&wall| !wall)
+    }
+
+    fn moves(&self, (position, facing): State, costs: Costs) -> Vec<(State, u64)> {
+        let mut moves = vec![
+            ((position, facing.turn_left()), costs.turn),
+            ((position, facing.turn_right()), costs.turn),
+        ];
+        if let Some(next) = facing.step(position).filter(|&next| self.is_open(next)) {
+            moves.push(((next, facing), costs.forward));
+        }
+        moves
+    }
+
+    /// Dijkstra over positions and orientations, remembering every equally cheap way into a
+    /// state, then walking those links back from the end.
+    fn solve(&self, costs: Costs, facing: Direction) -> Result<Solution> {
+        // Free moves would let equally cheap paths loop forever
+        if costs.forward == 0 || costs.turn == 0 {
+            bail!("Moving and turning must both cost something");
+        }
+
+        let start = (self.start, facing);
+        let mut scores: HashMap<State, u64> = HashMap::from([(start, 0)]);
+        let mut came_from: HashMap<State, Vec<State>> = HashMap::new();
+        let mut queue = BinaryHeap::from([Reverse((0, start))]);
+        let mut best = None;
+
+        while let Some(Reverse((score, state))) = queue.pop() {
+            if score > scores[&state] || best.is_some_and(|best| score > best) {
+                continue;
+            }
+            if state.0 == self.end {
+                best = Some(score);
+                continue;
+            }
+
+            for (next, cost) in self.moves(state, costs) {
+                let next_score = score + cost;
+                match scores.get(&next) {
+                    Some(&known) if next_score > known => {}
+                    Some(&known) if next_score == known => {
+                        came_from.entry(next).or_default().push(state)
+                    }
+                    _ => {
+                        scores.insert(next, next_score);
+                        came_from.insert(next, vec![state]);
+                        queue.push(Reverse((next_score, next)));
+                    }
+                }
+            }
+        }
+
+        let score = best.ok_or_else(|| anyhow!("The end cannot be reached from the start"))?;
+        let ends = Direction::ALL
+            .into_iter()
+            .map(|d| (self.end, d))
+            .filter(|state| scores.get(state) == Some(&score))
+            .collect::<Vec<State>>();
+
+        // Keep only the links between states on a best path
+        let mut seen: HashSet<State> = ends.iter().copied().collect();
+        let mut queue = ends.iter().copied().collect::<VecDeque<State>>();
+        while let Some(state) = queue.pop_front() {
+            for &previous in came_from.get(&state).into_iter().flatten() {
+                if seen.insert(previous) {
+                    queue.push_back(previous);
+                }
+            }
+        }
+        came_from.retain(|state, _| seen.contains(state));
+        scores.retain(|state, _| seen.contains(state));
+
+        Ok(Solution {
+            score,
+            tiles: seen.into_iter().map(|(position, _)| position).collect(),
+            start,
+            ends,
+            came_from,
+            scores,
+        })
+    }
+
+    /// The maze with the best path tiles marked `O`, as in the puzzle. With `path`, the tiles
+    /// of that path show the way the reindeer faces instead.
+    fn render(&self, solution: &Solution, path: Option<&[State]>) -> String {
+        let mut tiles = self
+            .walls
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&wall| if wall { '#' } else { '.' })
+                    .collect_vec()
+            })
+            .collect_vec();
+        for &(r, c) in solution.tiles.iter() {
+            tiles[r][c] = 'O';
+        }
+        if let Some(path) = path {
+            for &((r, c), facing) in path.iter() {
+                tiles[r][c] = facing.arrow();
+            }
+            tiles[self.start.0][self.start.1] = 'S';
+            tiles[self.end.0][self.end.1] = 'E';
+        }
+
+        tiles
+            .iter()
+            .map(|row| row.iter().collect::<String>() + "\n")
+            .collect()
+    }
+}
+
+impl Solution {
+    /// One of the best paths, as the states it goes through from the start.
+    fn path(&self) -> Vec<State> {
+        let mut path = vec![self.ends[0]];
+        while let Some(previous) = self.came_from.get(path.last().unwrap()) {
+            path.push(previous[0]);
+        }
+        path.reverse();
+        path
+    }
+
+    /// Distinct best paths, as the tiles they visit. Scores only grow along a path, so
+    /// visiting states by score sees every predecessor first. Turning on the spot visits no
+    /// new tile, so each state adds up the arrivals on its tile it can be turned to from,
+    /// each once: a U-turn to the left and one to the right make the same path.
+    fn path_count(&self) -> BigUint {
+        let mut order = self.scores.iter().collect_vec();
+        order.sort_unstable_by_key(|(_, &score)| score);
+
+        let mut arrivals: HashMap<State, BigUint> =
+            HashMap::from([(self.start, BigUint::from(1u8))]);
+        let mut counts: HashMap<State, BigUint> = HashMap::new();
+        for (&state, _) in order {
+            if state != self.start {
+                let arrived = self
+                    .came_from
+                    .get(&state)
+                    .into_iter()
+                    .flatten()
+                    .filter(|previous| previous.0 != state.0)
+                    .map(|previous| &counts[previous])
+                    .sum();
+                arrivals.insert(state, arrived);
+            }
+            let count = self
+                .turned_from(state)
+                .iter()
+                .map(|turned| &arrivals[turned])
+                .sum();
+            counts.insert(state, count);
+        }
+
+        self.ends.iter().map(|end| &counts[end]).sum()
+    }
+
+    /// The state and those on its tile that a best path turns it from.
+    fn turned_from(&self, state: State) -> HashSet<State> {
+        let mut turned = HashSet::from([state]);
+        let mut stack = vec![state];
+        while let Some(current) = stack.pop() {
+            for &previous in self.came_from.get(&current).into_iter().flatten() {
+                if previous.0 == state.0 && turned.insert(previous) {
+                    stack.push(previous);
+                }
+            }
+        }
+        turned
+    }
+}
@@ -1,5 +1,6 @@
-use adv_code_2024::start_day;
-use anyhow::{anyhow, Result};
+use adv_code_2024::animation::{every_option, Animation, Frame};
+use adv_code_2024::{cli_option, start_day};
+use anyhow::{anyhow, bail, Context, Result};
 use code_timing_macros::time_snippet;
 use colored::Colorize;
 use const_format::concatcp;
@@ -51,7 +52,7 @@ async fn main() -> anyhow::Result<()> {
     println!("=== Part 1 ===");
 
     async fn part1(input: &str, size: usize, limit: usize) -> Result<u32> {
-        let grid = parse(input, size, limit);
+        let grid = parse(input, size, limit)?;
         let path = grid.a_star_search().unwrap();
         //grid.print_with_path(&path.0);
         Ok(path.1 - 1)
@@ -59,10 +60,10 @@ async fn main() -> anyhow::Result<()> {
 
     async fn part2(input: &str, size: usize, limit: usize) -> Result<String> {
         for i in limit..10000000 {
-            let grid = parse(input, size, i);
+            let grid = parse(input, size, i)?;
             let path = grid.a_star_search();
             if path.is_none() {
-                return Ok(input.lines().collect_vec()[i - 1].to_string());
+                return Ok(falling_bytes(input).collect_vec()[i - 1].to_string());
             }
         }
 
@@ -73,6 +74,16 @@ async fn main() -> anyhow::Result<()> {
     println!("Test Result 1 = {}", result);
     assert_eq!(22, result);
 
+    // Blank lines are not bytes, and bytes must land in the memory space
+    assert_eq!(22, part1(&TEST.replace("\n", "\n\n"), 6, 12).await?);
+    let error = parse("1,1\n7,0\n", 6, 2).err().unwrap();
+    println!("{}", error);
+    assert_eq!(
+        "Byte 2 at 7,0 is outside the 7x7 memory space",
+        error.to_string()
+    );
+    assert!(parse("1;1\n", 6, 1).is_err());
+
     let mut input_file = File::open(INPUT_FILE)?;
     let mut buffer = Vec::new();
     input_file.read_to_end(&mut buffer)?;
@@ -92,6 +103,10 @@ async fn main() -> anyhow::Result<()> {
     println!("Result 2 = {}", result);
     assert_eq!("22,33", result);
 
+    if let Some(path) = cli_option("animate") {
+        animate(&input, 70, every_option(10)?, &path)?;
+    }
+
     anyhow::Ok(())
 }
 
@@ -323,25 +338,80 @@ impl Grid {
     }
 }
 
-#[allow(unused_assignments)]
-fn parse(input: &str, size: usize, limit: usize) -> Grid {
-    let mut grid = Grid::new(size);
+/// Writes a GIF or APNG of the bytes falling, `every` bytes per frame, with the shortest
+/// path drawn until the exit gets cut off.
+fn animate(input: &str, size: usize, every: usize, path: &str) -> Result<()> {
+    // Palette: safe, corrupted, latest bytes, path
+    let palette = vec![[15, 15, 35], [90, 90, 110], [255, 200, 0], [0, 200, 255]];
+    let mut animation = Animation::new(palette, 60);
+    animation.set_scale(6);
+
+    let bytes = falling_bytes(input).collect_vec();
+    let mut limit = 0;
+    loop {
+        let grid = parse(input, size, limit)?;
+        let shortest = grid.a_star_search();
+
+        let mut frame = Frame::new(size + 1, size + 1);
+        for (r, row) in grid.map.iter().enumerate() {
+            for (c, &v) in row.iter().enumerate() {
+                if v > 1 {
+                    frame.set(c, r, 1)?;
+                }
+            }
+        }
+        for line in &bytes[limit.saturating_sub(every)..limit] {
+            let (c, r) = parse_byte(line)?;
+            frame.set(c, r, 2)?;
+        }
+        if let Some((points, _)) = &shortest {
+            for point in points {
+                frame.set(point.c, point.r, 3)?;
+            }
+        }
+        animation.push(frame)?;
 
-    for (i, line) in input.lines().enumerate() {
-        if i == limit {
+        if shortest.is_none() || limit >= bytes.len() {
             break;
         }
+        limit = (limit + every).min(bytes.len());
+    }
+
+    animation.save(path)
+}
+
+/// The lines giving a falling byte, without blank ones.
+fn falling_bytes(input: &str) -> impl Iterator<Item = &str> {
+    input.lines().filter(|line| !line.trim().is_empty())
+}
 
-        let coordinates = line.split(',').collect_vec();
-        let c = coordinates[0].parse::<usize>().unwrap();
-        let r = coordinates[1].parse::<usize>().unwrap();
-        let set_result = grid.set_value(r, c, 2);
-        if !set_result.unwrap() {
-            panic!();
+#[allow(unused_assignments)]
+fn parse(input: &str, size: usize, limit: usize) -> Result<Grid> {
+    let mut grid = Grid::new(size);
+
+    for (i, line) in falling_bytes(input).take(limit).enumerate() {
+        let (c, r) = parse_byte(line).with_context(|| format!("Byte {}", i + 1))?;
+        if grid.set_value(r, c, 2) != Some(true) {
+            bail!(
+                "Byte {} at {},{} is outside the {}x{} memory space",
+                i + 1,
+                c,
+                r,
+                size + 1,
+                size + 1
+            );
         }
     }
 
     //grid.print();
 
-    grid
+    Ok(grid)
+}
+
+/// Reads an `X,Y` line as the column and row of a byte.
+fn parse_byte(line: &str) -> Result<(usize, usize)> {
+    let (c, r) = line
+        .split_once(',')
+        .ok_or_else(|| anyhow!("expected 'X,Y', found '{}'", line))?;
+    Ok((c.trim().parse()?, r.trim().parse()?))
 }
@@ -1,5 +1,5 @@
-use adv_code_2024::start_day;
-use anyhow::Result;
+use adv_code_2024::{cli_flag, cli_option, start_day};
+use anyhow::{anyhow, Result};
 use code_timing_macros::time_snippet;
 use colored::Colorize;
 use const_format::concatcp;
@@ -67,9 +67,110 @@ async fn main() -> Result<()> {
     println!("Result 2 = {}", result);
     assert_eq!(961364, result);
 
+    println!("=== Cheats ===");
+
+    async fn list(input: &str, max_distance: usize, min_saving: usize) -> Result<Vec<Cheat>> {
+        let grid = parse(input);
+        Ok(grid.cheats(max_distance, min_saving).await)
+    }
+
+    // Worked example: with 2 picoseconds of cheating there is exactly one cheat saving 64
+    let cheats = list(TEST, 2, 64).await?;
+    assert_eq!(1, cheats.len());
+    assert_eq!(64, cheats[0].saving);
+    parse(TEST).print_cheat(&cheats[0]);
+
+    // Worked example: with 20 picoseconds, 3 cheats save 76 picoseconds
+    let cheats = list(TEST, 20, 76).await?;
+    assert_eq!(3, cheats.len());
+
+    // Listing the cheats of the real input is only done on request, there are about as many
+    // as the part 2 answer
+    let limit = cli_option("limit")
+        .map(|value| value.parse::<usize>())
+        .transpose()?;
+    let show = cli_option("show")
+        .map(|value| value.parse::<usize>())
+        .transpose()?;
+    if cli_flag("cheats") || limit.is_some() || show.is_some() {
+        let max_distance = match cli_option("max-distance") {
+            Some(value) => value.parse()?,
+            None => 20,
+        };
+        let min_saving = match cli_option("min-saving") {
+            Some(value) => value.parse()?,
+            None => 100,
+        };
+        let grid = parse(&input);
+        let cheats = grid.cheats(max_distance, min_saving).await;
+        println!(
+            "{} cheats save at least {} picoseconds",
+            cheats.len(),
+            min_saving
+        );
+
+        if cli_flag("cheats") || limit.is_some() {
+            for (i, cheat) in cheats.iter().enumerate().take(limit.unwrap_or(usize::MAX)) {
+                println!("{:>6}: {}", i, cheat);
+            }
+        }
+
+        if let Some(index) = show {
+            let cheat = cheats
+                .get(index)
+                .ok_or_else(|| anyhow!("There are only {} cheats", cheats.len()))?;
+            grid.print_cheat(cheat);
+        }
+    }
+
     anyhow::Ok(())
 }
 
+/// A shortcut through the walls, from a track position to a later one.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Cheat {
+    start: Point,
+    end: Point,
+    saving: usize,
+}
+
+impl Display for Cheat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {} saves {} picoseconds",
+            self.start, self.end, self.saving
+        )
+    }
+}
+
+impl Cheat {
+    /// Cells crossed by the cheat, moving along rows first and then along columns.
+    fn path(&self) -> Vec<Point> {
+        let mut path = vec![self.start];
+        let mut current = self.start;
+
+        while current.r != self.end.r {
+            current.r = if current.r < self.end.r {
+                current.r + 1
+            } else {
+                current.r - 1
+            };
+            path.push(current);
+        }
+        while current.c != self.end.c {
+            current.c = if current.c < self.end.c {
+                current.c + 1
+            } else {
+                current.c - 1
+            };
+            path.push(current);
+        }
+
+        path
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct Node {
     position: Point,
@@ -246,6 +347,24 @@ impl Grid {
 
     async fn cheat(&self, max_distance: usize, limit: usize) -> u32 {
         let mut savings: HashMap<usize, u32> = HashMap::new();
+        for cheat in self.cheats(max_distance, limit).await {
+            *savings.entry(cheat.saving).or_insert(0) += 1;
+        }
+
+        for k in savings.keys().sorted() {
+            println!(
+                "There are {} cheats that save {} picoseconds",
+                savings[k], k
+            )
+        }
+
+        savings.values().sum()
+    }
+
+    /// Every distinct cheat of at most `max_distance` picoseconds saving at least `limit`,
+    /// sorted by saving (biggest first) and then by start and end positions.
+    async fn cheats(&self, max_distance: usize, limit: usize) -> Vec<Cheat> {
+        let mut cheats: Vec<Cheat> = Vec::new();
         let honest_path = self.a_star_search(None).await.unwrap();
 
         for sp_i in 0..honest_path.0.len() {
@@ -269,22 +388,26 @@ impl Grid {
                     continue;
                 }
 
-                if let Some(current_savings) = savings.get_mut(&saving_on_honest_path) {
-                    *current_savings += 1;
-                } else {
-                    savings.insert(saving_on_honest_path, 1);
-                }
+                cheats.push(Cheat {
+                    start: sp,
+                    end: ep,
+                    saving: saving_on_honest_path,
+                });
             }
         }
 
-        for k in savings.keys().sorted() {
-            println!(
-                "There are {} cheats that save {} picoseconds",
-                savings[k], k
-            )
-        }
+        cheats.sort_by(|a, b| {
+            b.saving
+                .cmp(&a.saving)
+                .then((a.start.r, a.start.c).cmp(&(b.start.r, b.start.c)))
+                .then((a.end.r, a.end.c).cmp(&(b.end.r, b.end.c)))
+        });
+        cheats
+    }
 
-        savings.values().sum()
+    fn print_cheat(&self, cheat: &Cheat) {
+        println!("{}", cheat);
+        self.print_with_path(&cheat.path());
     }
 
     async fn a_star_search(&self, cheat_point: Option<&Point>) -> Option<(Vec<Point>, u32)> {
@@ -0,0 +1,250 @@
+use adv_code_2024::grid::Direction;
+use adv_code_2024::*;
+use anyhow::*;
+use code_timing_macros::time_snippet;
+use const_format::concatcp;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+const DAY: &str = "21";
+const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
+
+const TEST: &str = "\
+029A
+980A
+179A
+456A
+379A
+";
+
+const NUMERIC: [&str; 4] = ["789", "456", "123", " 0A"];
+const DIRECTIONAL: [&str; 2] = [" ^A", "<v>"];
+
+// Sequences longer than this are only counted, never spelled out
+const MAX_PRINTED_PRESSES: u64 = 1_000_000;
+
+fn main() -> Result<()> {
+    start_day(DAY);
+
+    println!("=== Part 1 ===");
+
+    fn part1(input: &str) -> Result<u64> {
+        let mut chain = KeypadChain::new(2);
+        complexity(input, &mut chain)
+    }
+
+    fn part2(input: &str) -> Result<u64> {
+        let mut chain = KeypadChain::new(25);
+        complexity(input, &mut chain)
+    }
+
+    let result = part1(TEST)?;
+    println!("Test Result 1 = {}", result);
+    assert_eq!(126384, result);
+
+    let mut chain = KeypadChain::new(2);
+    for (code, presses) in [("029A", 68), ("980A", 60), ("179A", 68), ("456A", 64), ("379A", 64)] {
+        let sequence = chain.sequence(code)?;
+        println!("{}: {}", code, sequence);
+        assert_eq!(presses, sequence.len());
+    }
+
+    let result = part2(TEST)?;
+    println!("Test Result 2 = {}", result);
+    assert_eq!(154115708116294, result);
+
+    let mut input_file = File::open(INPUT_FILE)?;
+    let mut buffer = Vec::new();
+    input_file.read_to_end(&mut buffer)?;
+    let input = String::from_utf8_lossy(&buffer);
+
+    let result = time_snippet!(part1(&input)?);
+    println!("Result 1 = {}", result);
+
+    let result = time_snippet!(part2(&input)?);
+    println!("Result 2 = {}", result);
+
+    if cli_flag("show") {
+        let robots = match cli_option("robots") {
+            Some(value) => value.parse()?,
+            None => 2,
+        };
+        let mut chain = KeypadChain::new(robots);
+        for code in codes(&input) {
+            println!("{}: {}", code, chain.sequence(code)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn codes(input: &str) -> Vec<&str> {
+    input
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect_vec()
+}
+
+fn complexity(input: &str, chain: &mut KeypadChain) -> Result<u64> {
+    let mut total = 0;
+    for code in codes(input) {
+        let numeric = code
+            .trim_end_matches('A')
+            .parse::<u64>()
+            .with_context(|| format!("Code '{}' has no numeric part", code))?;
+        total += chain.presses(code)? * numeric;
+    }
+
+    Ok(total)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Keypad {
+    Numeric,
+    Directional,
+}
+
+impl Keypad {
+    fn layout(&self) -> &'static [&'static str] {
+        match self {
+            Keypad::Numeric => &NUMERIC,
+            Keypad::Directional => &DIRECTIONAL,
+        }
+    }
+
+    fn position(&self, key: char) -> Result<(usize, usize)> {
+        for (r, row) in self.layout().iter().enumerate() {
+            if let Some(c) = row.chars().position(|k| k == key && k != ' ') {
+                return Ok((r, c));
+            }
+        }
+
+        Err(anyhow!("There is no '{}' key on the {:?} keypad", key, self))
+    }
+
+    fn is_gap(&self, (r, c): (usize, usize)) -> bool {
+        self.layout()[r].as_bytes()[c] == b' '
+    }
+
+    /// The button sequences that move the arm from `from` to `to` and press it: all the
+    /// horizontal moves then all the vertical ones, or the other way round, avoiding the gap.
+    fn paths(&self, from: char, to: char) -> Result<Vec<String>> {
+        let (r1, c1) = self.position(from)?;
+        let (r2, c2) = self.position(to)?;
+
+        let vertical = if r2 < r1 { Direction::Up } else { Direction::Down };
+        let horizontal = if c2 < c1 { Direction::Left } else { Direction::Right };
+        let vertical = vertical.arrow().to_string().repeat(r1.abs_diff(r2));
+        let horizontal = horizontal.arrow().to_string().repeat(c1.abs_diff(c2));
+
+        let mut paths = Vec::new();
+        if !self.is_gap((r1, c2)) {
+            paths.push(format!("{}{}A", horizontal, vertical));
+        }
+        if !self.is_gap((r2, c1)) {
+            paths.push(format!("{}{}A", vertical, horizontal));
+        }
+        paths.dedup();
+
+        Ok(paths)
+    }
+}
+
+/// The numeric keypad robot, driven through `robots` directional keypads operated by robots
+/// and a last directional keypad operated by us.
+struct KeypadChain {
+    robots: usize,
+    // Presses needed at our keypad to move a directional arm from one key to another and
+    // press it, with the given number of directional keypads above it
+    costs: HashMap<(char, char, usize), u64>,
+}
+
+impl KeypadChain {
+    fn new(robots: usize) -> Self {
+        Self {
+            robots,
+            costs: HashMap::new(),
+        }
+    }
+
+    /// Buttons we have to press to get `code` typed on the numeric keypad.
+    fn presses(&mut self, code: &str) -> Result<u64> {
+        self.keys_cost(Keypad::Numeric, code, self.robots + 1)
+    }
+
+    fn keys_cost(&mut self, keypad: Keypad, keys: &str, layers: usize) -> Result<u64> {
+        if layers == 0 {
+            return Ok(keys.len() as u64);
+        }
+
+        let mut total = 0;
+        let mut from = 'A';
+        for to in keys.chars() {
+            total += self.move_cost(keypad, from, to, layers)?;
+            from = to;
+        }
+
+        Ok(total)
+    }
+
+    fn move_cost(&mut self, keypad: Keypad, from: char, to: char, layers: usize) -> Result<u64> {
+        if keypad == Keypad::Directional {
+            if let Some(&cost) = self.costs.get(&(from, to, layers)) {
+                return Ok(cost);
+            }
+        }
+
+        let mut best = u64::MAX;
+        for path in keypad.paths(from, to)? {
+            best = best.min(self.keys_cost(Keypad::Directional, &path, layers - 1)?);
+        }
+
+        if keypad == Keypad::Directional {
+            self.costs.insert((from, to, layers), best);
+        }
+
+        Ok(best)
+    }
+
+    /// The buttons we press to type `code`, only for chains short enough to print.
+    fn sequence(&mut self, code: &str) -> Result<String> {
+        let presses = self.presses(code)?;
+        if presses > MAX_PRINTED_PRESSES {
+            bail!(
+                "Typing {} through {} robots takes {} presses, too many to print",
+                code,
+                self.robots,
+                presses
+            );
+        }
+
+        self.keys_sequence(Keypad::Numeric, code, self.robots + 1)
+    }
+
+    fn keys_sequence(&mut self, keypad: Keypad, keys: &str, layers: usize) -> Result<String> {
+        if layers == 0 {
+            return Ok(keys.to_string());
+        }
+
+        let mut sequence = String::new();
+        let mut from = 'A';
+        for to in keys.chars() {
+            let mut best: Option<(u64, String)> = None;
+            for path in keypad.paths(from, to)? {
+                let cost = self.keys_cost(Keypad::Directional, &path, layers - 1)?;
+                if best.as_ref().is_none_or(|(b, _)| cost < *b) {
+                    best = Some((cost, path));
+                }
+            }
+
+            let (_, path) = best.unwrap();
+            sequence.push_str(&self.keys_sequence(Keypad::Directional, &path, layers - 1)?);
+            from = to;
+        }
+
+        Ok(sequence)
+    }
+}
@@ -2,8 +2,10 @@ use adv_code_2024::*;
 use anyhow::*;
 use code_timing_macros::time_snippet;
 use const_format::concatcp;
-use itertools::Itertools;
-use std::collections::{HashMap, HashSet};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::ops::BitXor;
@@ -25,6 +27,12 @@ const TEST2: &str = "\
 2024
 ";
 
+const TEST3: &str = "\
+720
+1427
+1657
+";
+
 fn main() -> Result<()> {
     start_day(DAY);
 
@@ -35,7 +43,9 @@ fn main() -> Result<()> {
     }
 
     fn part2(input: &str) -> Result<u64> {
-        produce_last(input)
+        let best = produce_last(input)?;
+        println!("Best sequence: {}", best);
+        Ok(best.bananas)
     }
 
     let result = part1(TEST)?;
@@ -45,6 +55,55 @@ fn main() -> Result<()> {
     let result = part2(TEST2)?;
     println!("Test Result 2 = {}", result);
     assert_eq!(23, result);
+    assert_eq!([-2, 1, -1, 3], produce_last(TEST2)?.sequence);
+
+    // 720 sells for 8 at its very first window, which starts at the initial price. Leaving
+    // out that window and the last one, as the first solution did, the best total was 19
+    let best = produce_last(TEST3)?;
+    assert_eq!([8, -5, 1, 4], best.sequence);
+    assert_eq!(24, best.bananas);
+
+    // The puzzle's walk through of 123: the first change comes from the initial secret
+    let mut market = Market::new();
+    market.trade(&[123], 9);
+    assert_eq!(4, market.bananas_for([-3, 6, -1, -1]));
+    assert_eq!(
+        Some(Best {
+            sequence: [-1, -1, 0, 2],
+            bananas: 6
+        }),
+        market.best()
+    );
+
+    println!("=== Generator ===");
+
+    let mut prng = Prng::new(123)?;
+    prng.jump(10);
+    assert_eq!(5908254, prng.secret());
+    prng.previous();
+    assert_eq!(7753432, prng.secret());
+    prng.jump(-9);
+    assert_eq!(123, prng.secret());
+
+    let mut prng = Prng::new(1)?;
+    prng.jump(2000);
+    assert_eq!(8685429, prng.secret());
+    prng.jump(-1_000_000_000);
+    prng.jump(1_000_000_000);
+    assert_eq!(8685429, prng.secret());
+    assert_eq!(16777215, prng.period());
+    assert_eq!(1, Prng::new(0)?.period());
+
+    if let Some(seed) = cli_option("seed") {
+        let mut prng = Prng::new(seed.parse()?)?;
+        let steps = match cli_option("jump") {
+            Some(value) => value.parse()?,
+            None => 0,
+        };
+        prng.jump(steps);
+        println!("Secret after {} steps = {}", steps, prng.secret());
+        println!("Period = {}", prng.period());
+    }
 
     let mut input_file = File::open(INPUT_FILE)?;
     let mut buffer = Vec::new();
@@ -62,97 +121,324 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn produce_secrets(input: &str) -> Result<u64> {
-    let secrets = input
+const MODULUS: u32 = 16777216;
+const STEPS: usize = 2000;
+// Each price change is in -9..=9, so four of them fit a base-19 number
+const SEQUENCES: usize = 19 * 19 * 19 * 19;
+// Buyers evolved in lockstep by the same worker
+const BATCH: usize = 256;
+
+fn parse(input: &str) -> Result<Vec<u32>> {
+    input
         .lines()
-        .collect_vec()
-        .iter()
-        .map(|l| l.parse::<u64>().unwrap())
-        .collect_vec();
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            let secret = l
+                .trim()
+                .parse::<u32>()
+                .with_context(|| format!("Invalid secret '{}'", l))?;
+            Ok(Prng::new(secret)?.secret())
+        })
+        .collect()
+}
+
+fn produce_secrets(input: &str) -> Result<u64> {
+    let secrets = parse(input)?;
 
     let total = secrets
-        .iter()
-        .map(|&s| {
-            let mut secret: u64 = s;
-            for _ in 0..2000 {
-                secret = prune(mix(secret, secret * 64));
-                secret = prune(mix(secret, secret.div_euclid(32)));
-                secret = prune(mix(secret, secret * 2048));
+        .par_chunks(BATCH)
+        .map(|chunk| {
+            let mut batch = chunk.to_vec();
+            for _ in 0..STEPS {
+                evolve(&mut batch);
             }
-            secret
+            batch.iter().map(|&s| s as u64).sum::<u64>()
         })
         .sum::<u64>();
 
     Ok(total)
 }
 
-fn produce_last(input: &str) -> Result<u64> {
-    let secrets = input
-        .lines()
-        .collect_vec()
-        .iter()
-        .map(|l| l.parse::<u64>().unwrap())
-        .collect_vec();
+fn produce_last(input: &str) -> Result<Best> {
+    let secrets = parse(input)?;
 
-    let mut map: HashMap<Price, u64> = HashMap::new();
-    let mut seen: HashSet<(u64, Price)> = HashSet::new();
+    let market = secrets
+        .par_chunks(BATCH)
+        .map(|chunk| {
+            let mut market = Market::new();
+            market.trade(chunk, STEPS);
+            market
+        })
+        .reduce(Market::new, Market::merge);
 
-    for s in secrets {
-        let mut last_digits: Vec<i32> = Vec::new();
+    market
+        .best()
+        .ok_or_else(|| anyhow!("No buyer sees four price changes"))
+}
 
-        let mut secret: u64 = s;
-        for _ in 0..2000 {
-            secret = prune(mix(secret, secret * 64));
-            secret = prune(mix(secret, secret.div_euclid(32)));
-            secret = prune(mix(secret, secret * 2048));
+/// The winning sequence of four price changes and the bananas it buys.
+#[derive(Debug, Eq, PartialEq)]
+struct Best {
+    sequence: [i8; 4],
+    bananas: u64,
+}
 
-            let last_digit = (secret % 10) as i32;
-            last_digits.push(last_digit);
+impl Display for Best {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{} ({} bananas)",
+            self.sequence[0], self.sequence[1], self.sequence[2], self.sequence[3], self.bananas
+        )
+    }
+}
+
+/// Bananas collected by every sequence of four price changes, indexed in base 19.
+struct Market {
+    bananas: Vec<u32>,
+    // Stamp of the last buyer that sold on each sequence, 0 when none did
+    last_seen: Vec<u32>,
+}
+
+impl Market {
+    fn new() -> Self {
+        Self {
+            bananas: vec![0; SEQUENCES],
+            last_seen: vec![0; SEQUENCES],
+        }
+    }
+
+    /// Sells for every buyer of the batch, each one at the first occurrence of a sequence.
+    /// Prices start with the initial secret, so the first change leads to the first new one.
+    /// Secrets evolve in lockstep, but buyers sell one after the other: a stamp only tells
+    /// the current buyer's sequences apart while no other buyer is selling.
+    fn trade(&mut self, secrets: &[u32], steps: usize) {
+        let buyers = secrets.len();
+        let mut batch = secrets.to_vec();
+        // Prices of the whole batch, step after step
+        let mut prices = Vec::with_capacity((steps + 1) * buyers);
+        prices.extend(batch.iter().map(|s| s % 10));
+        for _ in 0..steps {
+            evolve(&mut batch);
+            prices.extend(batch.iter().map(|s| s % 10));
         }
 
-        calculate(s, &last_digits, &mut map, &mut seen);
+        for buyer in 0..buyers {
+            let stamp = buyer as u32 + 1;
+            let mut key = 0;
+            for step in 1..=steps {
+                let price = prices[step * buyers + buyer];
+                let delta = (price + 9 - prices[(step - 1) * buyers + buyer]) as usize;
+                key = (key * 19 + delta) % SEQUENCES;
+
+                if step >= 4 && self.last_seen[key] != stamp {
+                    self.last_seen[key] = stamp;
+                    self.bananas[key] += price;
+                }
+            }
+        }
+
+        // Stamps are per batch, so a following batch must not see them
+        self.last_seen.fill(0);
+    }
+
+    fn bananas_for(&self, sequence: [i8; 4]) -> u32 {
+        let key = sequence
+            .iter()
+            .fold(0, |key, &change| key * 19 + (change + 9) as usize);
+        self.bananas[key]
+    }
+
+    fn merge(mut self, other: Market) -> Market {
+        for (total, bananas) in self.bananas.iter_mut().zip(other.bananas) {
+            *total += bananas;
+        }
+        self
     }
 
-    let max = map.values().max().unwrap();
+    fn best(&self) -> Option<Best> {
+        let (key, &bananas) = self
+            .bananas
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b > 0)
+            .max_by_key(|(key, &b)| (b, std::cmp::Reverse(*key)))?;
+
+        let mut sequence = [0i8; 4];
+        let mut rest = key;
+        for change in sequence.iter_mut().rev() {
+            *change = (rest % 19) as i8 - 9;
+            rest /= 19;
+        }
 
-    Ok(*max)
+        Some(Best {
+            sequence,
+            bananas: bananas as u64,
+        })
+    }
 }
 
-#[derive(Eq, PartialEq, Hash)]
-struct Price {
-    a: i32,
-    b: i32,
-    c: i32,
-    d: i32,
+/// Moves every secret of the batch one step forward; a flat loop the compiler can vectorise.
+fn evolve(secrets: &mut [u32]) {
+    for secret in secrets.iter_mut() {
+        *secret = next_secret(*secret);
+    }
 }
 
-fn calculate(
-    key: u64,
-    ld: &[i32],
-    map: &mut HashMap<Price, u64>,
-    seen: &mut HashSet<(u64, Price)>,
-) {
-    for i in 0..ld.len() - 5 {
-        let a = ld[i + 1] - ld[i];
-        let b = ld[i + 2] - ld[i + 1];
-        let c = ld[i + 3] - ld[i + 2];
-        let d = ld[i + 4] - ld[i + 3];
-        let p = ld[i + 4] as u64;
+fn next_secret(secret: u32) -> u32 {
+    let secret = prune(mix(secret, secret << 6));
+    let secret = prune(mix(secret, secret >> 5));
+    prune(mix(secret, secret << 11))
+}
 
-        if !seen.insert((key, Price { a, b, c, d })) {
-            continue;
-        }
+fn previous_secret(secret: u32) -> u32 {
+    let secret = unshift_left(secret, 11);
+    let secret = unshift_right(secret, 5);
+    unshift_left(secret, 6)
+}
 
-        map.entry(Price { a, b, c, d })
-            .and_modify(|f| *f += p)
-            .or_insert(p);
+// Inverts `x ^ (x << shift)` by folding the shifted copies back in until they leave the 24 bits
+fn unshift_left(value: u32, shift: u32) -> u32 {
+    let mut result = value;
+    let mut offset = shift;
+    while offset < 24 {
+        result ^= value << offset;
+        offset += shift;
     }
+    prune(result)
 }
 
-fn mix(secret: u64, given: u64) -> u64 {
+fn unshift_right(value: u32, shift: u32) -> u32 {
+    let mut result = value;
+    let mut offset = shift;
+    while offset < 24 {
+        result ^= value >> offset;
+        offset += shift;
+    }
+    prune(result)
+}
+
+fn mix(secret: u32, given: u32) -> u32 {
     given.bitxor(secret)
 }
 
-fn prune(secret: u64) -> u64 {
-    secret % 16777216
+fn prune(secret: u32) -> u32 {
+    secret % MODULUS
+}
+
+/// The buyers' 24-bit generator. Every stage is a xor-shift, so a step is linear over GF(2)
+/// and can be inverted or raised to any power as a 24×24 bit matrix.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Prng {
+    secret: u32,
+}
+
+impl Prng {
+    fn new(seed: u32) -> Result<Self> {
+        if seed >= MODULUS {
+            bail!("Secret {} does not fit in 24 bits", seed);
+        }
+        Ok(Self { secret: seed })
+    }
+
+    fn secret(&self) -> u32 {
+        self.secret
+    }
+
+    fn next(&mut self) -> u32 {
+        self.secret = next_secret(self.secret);
+        self.secret
+    }
+
+    fn previous(&mut self) -> u32 {
+        self.secret = previous_secret(self.secret);
+        self.secret
+    }
+
+    /// Moves `steps` forward (or backwards when negative) in O(log steps).
+    fn jump(&mut self, steps: i64) {
+        let matrix = if steps < 0 {
+            BitMatrix::from_fn(previous_secret)
+        } else {
+            BitMatrix::from_fn(next_secret)
+        };
+        self.secret = matrix.pow(steps.unsigned_abs()).apply(self.secret);
+    }
+
+    /// Number of steps before the secret comes back, found with baby-step giant-step jumps.
+    fn period(&self) -> u64 {
+        let baby_steps = 1u64 << 12;
+
+        // Positions of the first baby steps, keeping the smallest offset for each secret
+        let mut seen: HashMap<u32, u64> = HashMap::new();
+        let mut walker = *self;
+        for j in 1..=baby_steps {
+            if walker.next() == self.secret {
+                return j;
+            }
+            seen.entry(walker.secret).or_insert(j);
+        }
+
+        // secret = M^(i·m + j)·secret  <=>  M^(-i·m)·secret = M^j·secret
+        let giant = BitMatrix::from_fn(previous_secret).pow(baby_steps);
+        let mut back = self.secret;
+        for i in 1.. {
+            back = giant.apply(back);
+            if back == self.secret {
+                return i * baby_steps;
+            }
+            if let Some(j) = seen.get(&back) {
+                return i * baby_steps + j;
+            }
+        }
+
+        unreachable!()
+    }
+}
+
+/// A 24×24 matrix over GF(2), stored as the images of the unit vectors.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct BitMatrix {
+    columns: [u32; 24],
+}
+
+impl BitMatrix {
+    fn identity() -> Self {
+        Self {
+            columns: std::array::from_fn(|j| 1 << j),
+        }
+    }
+
+    fn from_fn(f: impl Fn(u32) -> u32) -> Self {
+        Self {
+            columns: std::array::from_fn(|j| f(1 << j)),
+        }
+    }
+
+    fn apply(&self, vector: u32) -> u32 {
+        self.columns
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| vector & (1 << j) != 0)
+            .fold(0, |acc, (_, column)| acc ^ column)
+    }
+
+    fn multiply(&self, other: &BitMatrix) -> BitMatrix {
+        BitMatrix {
+            columns: other.columns.map(|column| self.apply(column)),
+        }
+    }
+
+    fn pow(&self, mut exponent: u64) -> BitMatrix {
+        let mut result = BitMatrix::identity();
+        let mut base = *self;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.multiply(&base);
+            }
+            base = base.multiply(&base);
+            exponent >>= 1;
+        }
+        result
+    }
 }
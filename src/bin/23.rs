@@ -3,16 +3,11 @@ use anyhow::*;
 use code_timing_macros::time_snippet;
 use const_format::concatcp;
 use itertools::Itertools;
-use petgraph::algo;
-use petgraph::data::Build;
-use petgraph::graphmap::NodeTrait;
-use petgraph::prelude::*;
-use petgraph::visit::{GetAdjacencyMatrix, IntoNodeIdentifiers, NodeRef};
-use std::collections::{HashMap, HashSet};
-use std::fmt::{format, Debug};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
-use std::ops::{BitXor, Index};
 
 const DAY: &str = "23";
 const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
@@ -58,186 +53,656 @@ fn main() -> Result<()> {
     println!("=== Part 1 ===");
 
     fn part1(input: &str) -> Result<usize> {
-        let total = mix(input);
+        let network = Network::parse(input)?;
+        let total = network.k_cliques(3, Some(&|n| n.starts_with('t'))).len();
         Ok(total)
     }
 
-    fn part2(input: &str) -> Result<u64> {
-        biggest_clique(input);
-        Ok(0)
+    fn part2(input: &str) -> Result<String> {
+        let network = Network::parse(input)?;
+        Ok(network.password())
     }
 
     let result = part1(TEST)?;
     println!("Test Result 1 = {}", result);
     assert_eq!(7, result);
+    assert_eq!(12, Network::parse(TEST)?.k_cliques(3, None).len());
 
     let result = part2(TEST)?;
     println!("Test Result 2 = {}", result);
-    //assert_eq!(23, result);
-    //
+    assert_eq!("co,de,ka,ta", result);
+
+    println!("=== Network ===");
+
+    let network = Network::parse(TEST)?;
+    let statistics = network.statistics();
+    println!("{}", statistics);
+    assert_eq!(16, statistics.nodes);
+    assert_eq!(32, statistics.edges);
+    assert_eq!(12, statistics.triangles);
+    assert_eq!(vec![16], statistics.components);
+
+    let reloaded = Network::parse(&network.to_adjacency_list())?;
+    assert_eq!(network.to_adjacency_list(), reloaded.to_adjacency_list());
+    assert_eq!(network.password(), reloaded.password());
+
+    // Names from adjacency lists can hold anything, exports must stay well formed
+    let odd = Network::parse("a\"b: x<&y\n")?;
+    assert!(odd.to_dot(&[]).contains(r#""a\"b" -- "x<&y""#));
+    assert!(odd.to_graphml().contains(r#"<node id="a&quot;b"/>"#));
+    assert!(odd.to_graphml().contains(r#"<node id="x&lt;&amp;y"/>"#));
+
     let mut input_file = File::open(INPUT_FILE)?;
     let mut buffer = Vec::new();
     input_file.read_to_end(&mut buffer)?;
     let input = String::from_utf8_lossy(&buffer);
 
-    // let result = time_snippet!(part1(&input)?);
-    // println!("Result 1 = {}", result);
-    // assert_eq!(16999668565, result);
+    let result1 = time_snippet!(part1(&input)?);
+    println!("Result 1 = {}", result1);
+
+    let result2 = time_snippet!(part2(&input)?);
+    println!("Result 2 = {}", result2);
 
-    let _ = time_snippet!(part2(&input)?);
-    // println!("Result 2 = {}", result);
-    // assert_eq!(1898, result);
+    // The puzzle input is not kept in the repository, so both answers are checked
+    // against plain searches that do not go through Bron–Kerbosch
+    let network = Network::parse(&input)?;
+    let has_t = |nodes: &[usize]| nodes.iter().any(|&n| network.names[n].starts_with('t'));
+    let triangles = network
+        .edges()
+        .iter()
+        .map(|&(n1, n2)| {
+            (n2 + 1..network.len())
+                .filter(|&n3| {
+                    network.adjacency[n1].contains(n3)
+                        && network.adjacency[n2].contains(n3)
+                        && has_t(&[n1, n2, n3])
+                })
+                .count()
+        })
+        .sum::<usize>();
+    assert_eq!(triangles, result1);
+
+    let largest = network.largest_clique();
+    assert_eq!(largest.join(","), result2);
+    assert!(network.k_cliques(largest.len(), None).contains(&largest));
+    assert!(network.k_cliques(largest.len() + 1, None).is_empty());
+
+    if cli_flag("stats") {
+        println!("{}", network.statistics());
+    }
+
+    if let Some(format) = cli_option("export") {
+        let output = match format.as_str() {
+            "dot" => network.to_dot(&[network.largest_clique()]),
+            "graphml" => network.to_graphml(),
+            "adjacency" => network.to_adjacency_list(),
+            _ => bail!(
+                "Unknown export format '{}', use dot, graphml or adjacency",
+                format
+            ),
+        };
+
+        match cli_option("output") {
+            Some(path) => std::fs::write(path, output)?,
+            None => print!("{}", output),
+        }
+    }
 
     Ok(())
 }
 
-fn mix(input: &str) -> usize {
-    let mut tokens: HashSet<String> = HashSet::new();
-    let mut graph: Graph<&str, i32, Undirected> = Graph::new_undirected();
-    let mut node_index: HashMap<String, NodeIndex> = HashMap::new();
+/// The LAN party computers, with the adjacency of each one kept as a bitset.
+struct Network {
+    names: Vec<String>,
+    adjacency: Vec<BitSet>,
+}
 
-    for ns in input.lines().collect_vec() {
-        let split = ns.split('-').collect_vec();
+impl Network {
+    /// Loads either `a-b` connection lines or an adjacency list with `a: b c` lines.
+    fn parse(input: &str) -> Result<Network> {
+        // Each node followed by its neighbours, so isolated nodes are kept too
+        let mut rows: Vec<(&str, Vec<&str>)> = Vec::new();
 
-        let node_1 = split[0];
-        let node_2 = split[1];
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
 
-        tokens.insert(node_1.to_string());
-        tokens.insert(node_2.to_string());
+            if let Some((node, neighbours)) = line.split_once(':') {
+                rows.push((node.trim(), neighbours.split_whitespace().collect_vec()));
+            } else {
+                let (node_1, node_2) = line
+                    .split_once('-')
+                    .ok_or_else(|| anyhow!("Line {} is not a connection: '{}'", i + 1, line))?;
+                rows.push((node_1.trim(), vec![node_2.trim()]));
+            }
+        }
 
-        if !node_index.contains_key(node_1) {
-            let n1 = graph.add_node(node_1);
-            node_index.insert(node_1.to_string(), n1);
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        let mut names: Vec<String> = Vec::new();
+        for name in rows
+            .iter()
+            .flat_map(|(node, neighbours)| std::iter::once(node).chain(neighbours.iter()))
+        {
+            index.entry(name).or_insert_with(|| {
+                names.push(name.to_string());
+                names.len() - 1
+            });
         }
 
-        if !node_index.contains_key(node_2) {
-            let n2 = graph.add_node(node_2);
-            node_index.insert(node_2.to_string(), n2);
+        let mut adjacency = vec![BitSet::new(names.len()); names.len()];
+        for (node, neighbours) in rows.iter() {
+            let n1 = index[node];
+            for neighbour in neighbours {
+                let n2 = index[neighbour];
+                if n1 != n2 {
+                    adjacency[n1].insert(n2);
+                    adjacency[n2].insert(n1);
+                }
+            }
         }
 
-        graph.update_edge(
-            node_index[&node_1.to_string()],
-            node_index[&node_2.to_string()],
-            1,
-        );
+        Ok(Network { names, adjacency })
     }
 
-    let mut total = 0;
-    let adj_matrix = graph.adjacency_matrix();
+    fn len(&self) -> usize {
+        self.names.len()
+    }
 
-    let combinations = tokens.iter().combinations(3).collect_vec();
-    for nodes in combinations.as_slice() {
-        let n1_t = nodes[0];
-        let n2_t = nodes[1];
-        let n3_t = nodes[2];
+    fn sorted_names(&self, nodes: &[usize]) -> Vec<&str> {
+        nodes
+            .iter()
+            .map(|&n| self.names[n].as_str())
+            .sorted()
+            .collect_vec()
+    }
 
-        if n1_t == n2_t || n2_t == n3_t || n1_t == n3_t {
-            continue;
+    /// Nodes ordered by repeatedly removing the one with the fewest remaining neighbours.
+    fn degeneracy_order(&self) -> Vec<usize> {
+        let mut degrees = self.adjacency.iter().map(|a| a.len()).collect_vec();
+        let mut removed = BitSet::new(self.len());
+        let mut order = Vec::with_capacity(self.len());
+
+        for _ in 0..self.len() {
+            let node = (0..self.len())
+                .filter(|&n| !removed.contains(n))
+                .min_by_key(|&n| degrees[n])
+                .unwrap();
+
+            removed.insert(node);
+            order.push(node);
+            for neighbour in self.adjacency[node].iter() {
+                degrees[neighbour] -= 1;
+            }
         }
 
-        if !n1_t.starts_with("t") && !n2_t.starts_with("t") && !n3_t.starts_with("t") {
-            continue;
+        order
+    }
+
+    /// Every maximal clique, as sorted node names, found lazily by Bron–Kerbosch with
+    /// Tomita pivoting, with the outer level walking the degeneracy order.
+    fn maximal_cliques(&self) -> MaximalCliques<'_> {
+        MaximalCliques {
+            network: self,
+            order: self.degeneracy_order(),
+            next_root: 0,
+            done: BitSet::new(self.len()),
+            stack: Vec::new(),
         }
+    }
 
-        let n1 = node_index[nodes[0]];
-        let n2 = node_index[nodes[1]];
-        let n3 = node_index[nodes[2]];
+    fn largest_clique(&self) -> Vec<&str> {
+        self.maximal_cliques()
+            .max_by(|a, b| a.len().cmp(&b.len()).then(b.cmp(a)))
+            .unwrap_or_default()
+    }
 
-        if graph.is_adjacent(&adj_matrix, n1, n2)
-            && graph.is_adjacent(&adj_matrix, n2, n3)
-            && graph.is_adjacent(&adj_matrix, n1, n3)
-        {
-            //println!("{}-{}-{}", &nodes[0], &nodes[1], &nodes[2]);
-            total += 1;
-        }
+    /// The LAN party password: the names of the largest clique, sorted and comma separated.
+    fn password(&self) -> String {
+        self.largest_clique().join(",")
     }
 
-    total
-}
+    /// Every clique of exactly `k` nodes, keeping only those with a node accepted by `filter`.
+    fn k_cliques(&self, k: usize, filter: Option<&dyn Fn(&str) -> bool>) -> Vec<Vec<&str>> {
+        let mut cliques: Vec<Vec<usize>> = Vec::new();
+
+        // Cliques are grown by increasing node id, so each one is found once
+        fn grow(
+            network: &Network,
+            k: usize,
+            clique: &mut Vec<usize>,
+            candidates: &BitSet,
+            cliques: &mut Vec<Vec<usize>>,
+        ) {
+            if clique.len() == k {
+                cliques.push(clique.clone());
+                return;
+            }
+
+            for node in candidates.iter() {
+                let mut next = candidates.intersection(&network.adjacency[node]);
+                next.retain_above(node);
+                if clique.len() + 1 + next.len() < k {
+                    continue;
+                }
+
+                clique.push(node);
+                grow(network, k, clique, &next, cliques);
+                clique.pop();
+            }
+        }
 
-fn biggest_clique(input: &str) {
-    let mut graph: GraphMap<&str, i32, Undirected> = GraphMap::new();
-    let mut node_index: HashSet<String> = HashSet::new();
+        if k > 0 {
+            let all = BitSet::full(self.len());
+            grow(self, k, &mut Vec::new(), &all, &mut cliques);
+        }
 
-    for ns in input.lines().collect_vec() {
-        let split = ns.split('-').collect_vec();
+        cliques
+            .iter()
+            .filter(|c| match filter {
+                Some(filter) => c.iter().any(|&n| filter(&self.names[n])),
+                None => true,
+            })
+            .map(|c| self.sorted_names(c))
+            .collect_vec()
+    }
 
-        let node_1 = split[0];
-        let node_2 = split[1];
+    fn edges(&self) -> Vec<(usize, usize)> {
+        (0..self.len())
+            .flat_map(|n1| {
+                self.adjacency[n1]
+                    .iter()
+                    .filter(move |&n2| n1 < n2)
+                    .map(move |n2| (n1, n2))
+            })
+            .collect_vec()
+    }
 
-        if !node_index.contains(node_1) {
-            graph.add_node(node_1);
-            node_index.insert(node_1.to_string());
+    /// Graphviz DOT, with the nodes and connections of the given cliques highlighted.
+    fn to_dot(&self, highlight: &[Vec<&str>]) -> String {
+        let highlighted = |name: &str| highlight.iter().any(|c| c.contains(&name));
+        let same_clique =
+            |a: &str, b: &str| highlight.iter().any(|c| c.contains(&a) && c.contains(&b));
+
+        let mut dot = String::from("graph lan {\n");
+        for name in self.names.iter().sorted() {
+            if highlighted(name) {
+                dot.push_str(&format!(
+                    "  {} [style=filled, fillcolor=orange];\n",
+                    dot_id(name)
+                ));
+            } else {
+                dot.push_str(&format!("  {};\n", dot_id(name)));
+            }
         }
+        for (n1, n2) in self.edges() {
+            let (a, b) = (&self.names[n1], &self.names[n2]);
+            if same_clique(a, b) {
+                dot.push_str(&format!(
+                    "  {} -- {} [color=red, penwidth=2];\n",
+                    dot_id(a),
+                    dot_id(b)
+                ));
+            } else {
+                dot.push_str(&format!("  {} -- {};\n", dot_id(a), dot_id(b)));
+            }
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
 
-        if !node_index.contains(node_2) {
-            graph.add_node(node_2);
-            node_index.insert(node_2.to_string());
+    fn to_graphml(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        xml.push_str("  <graph id=\"lan\" edgedefault=\"undirected\">\n");
+        for name in self.names.iter() {
+            xml.push_str(&format!("    <node id=\"{}\"/>\n", xml_escape(name)));
+        }
+        for (n1, n2) in self.edges() {
+            xml.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\"/>\n",
+                xml_escape(&self.names[n1]),
+                xml_escape(&self.names[n2])
+            ));
         }
+        xml.push_str("  </graph>\n</graphml>\n");
+
+        xml
+    }
 
-        graph.update_edge(node_1, node_2, 1);
+    /// One `node: neighbours` line per node, which `parse` reads back.
+    fn to_adjacency_list(&self) -> String {
+        (0..self.len())
+            .sorted_by_key(|&n| &self.names[n])
+            .map(|n| {
+                let neighbours = self.sorted_names(&self.adjacency[n].iter().collect_vec());
+                format!("{}: {}\n", self.names[n], neighbours.join(" "))
+            })
+            .collect()
     }
 
-    let mut bk = BronKerbosch::new(graph);
-    bk.compute();
+    fn components(&self) -> Vec<Vec<usize>> {
+        let mut seen = BitSet::new(self.len());
+        let mut components = Vec::new();
 
-    for c in bk
-        .max_cliques
-        .iter()
-        .sorted_by(|a, b| Ord::cmp(&b.len(), &a.len()))
-    {
-        let c = c.iter().copied().sorted().collect_vec().join(",");
-        println!("{}", c);
+        for start in 0..self.len() {
+            if seen.contains(start) {
+                continue;
+            }
+
+            seen.insert(start);
+            let mut component = vec![start];
+            let mut i = 0;
+            while i < component.len() {
+                for neighbour in self.adjacency[component[i]].iter() {
+                    if !seen.contains(neighbour) {
+                        seen.insert(neighbour);
+                        component.push(neighbour);
+                    }
+                }
+                i += 1;
+            }
+            components.push(component);
+        }
+
+        components
+    }
+
+    /// Triangles each node is part of.
+    fn node_triangles(&self) -> Vec<usize> {
+        let mut triangles = vec![0; self.len()];
+        for (n1, n2) in self.edges() {
+            let mut common = self.adjacency[n1].intersection(&self.adjacency[n2]);
+            common.retain_above(n2);
+            for n3 in common.iter() {
+                triangles[n1] += 1;
+                triangles[n2] += 1;
+                triangles[n3] += 1;
+            }
+        }
+        triangles
+    }
+
+    fn statistics(&self) -> Statistics {
+        let degrees = self.adjacency.iter().map(|a| a.len()).collect_vec();
+        let triangles = self.node_triangles();
+
+        let mut degree_distribution = BTreeMap::new();
+        for &degree in degrees.iter() {
+            *degree_distribution.entry(degree).or_insert(0) += 1;
+        }
+
+        let local_clustering = |n: usize| {
+            let pairs = degrees[n] * degrees[n].saturating_sub(1) / 2;
+            if pairs == 0 {
+                0.0
+            } else {
+                triangles[n] as f64 / pairs as f64
+            }
+        };
+        let average_clustering = if self.len() == 0 {
+            0.0
+        } else {
+            (0..self.len()).map(local_clustering).sum::<f64>() / self.len() as f64
+        };
+
+        let connected_triples: usize = degrees.iter().map(|d| d * d.saturating_sub(1) / 2).sum();
+        let total_triangles = triangles.iter().sum::<usize>() / 3;
+        let transitivity = if connected_triples == 0 {
+            0.0
+        } else {
+            (3 * total_triangles) as f64 / connected_triples as f64
+        };
+
+        Statistics {
+            nodes: self.len(),
+            edges: self.edges().len(),
+            degree_distribution,
+            components: self
+                .components()
+                .iter()
+                .map(|c| c.len())
+                .sorted()
+                .rev()
+                .collect_vec(),
+            triangles: total_triangles,
+            average_clustering,
+            transitivity,
+        }
     }
 }
 
-struct BronKerbosch<N: NodeTrait, E> {
-    graph: GraphMap<N, E, Undirected>,
-    max_cliques: Vec<HashSet<N>>,
+/// A node name as a quoted DOT identifier.
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
 }
 
-impl<N: NodeTrait, E> BronKerbosch<N, E> {
-    pub fn new(graphmap: GraphMap<N, E, Undirected>) -> BronKerbosch<N, E> {
-        BronKerbosch {
-            graph: graphmap,
-            max_cliques: Vec::new(),
+/// A node name made safe for an XML attribute value.
+fn xml_escape(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct Statistics {
+    nodes: usize,
+    edges: usize,
+    // Number of nodes for each degree
+    degree_distribution: BTreeMap<usize, usize>,
+    // Sizes of the connected components, biggest first
+    components: Vec<usize>,
+    triangles: usize,
+    average_clustering: f64,
+    transitivity: f64,
+}
+
+impl Display for Statistics {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "Nodes: {}", self.nodes)?;
+        writeln!(f, "Edges: {}", self.edges)?;
+        writeln!(f, "Degree distribution:")?;
+        for (degree, count) in self.degree_distribution.iter() {
+            writeln!(f, "  {:>4}: {}", degree, count)?;
         }
+        writeln!(
+            f,
+            "Connected components: {} (sizes {})",
+            self.components.len(),
+            self.components.iter().join(", ")
+        )?;
+        writeln!(f, "Triangles: {}", self.triangles)?;
+        writeln!(
+            f,
+            "Average clustering coefficient: {:.4}",
+            self.average_clustering
+        )?;
+        write!(f, "Global clustering coefficient: {:.4}", self.transitivity)
     }
+}
 
-    pub fn compute(&mut self) {
-        let p = self.graph.nodes().collect::<HashSet<N>>();
-        let r = HashSet::new();
-        let x = HashSet::new();
-        self.bronkerbosch(p, r, x);
-    }
+struct Frame {
+    r: Vec<usize>,
+    p: BitSet,
+    x: BitSet,
+    candidates: Vec<usize>,
+}
 
-    pub fn cliques(&self) -> &Vec<HashSet<N>> {
-        &self.max_cliques
+impl Frame {
+    /// Only the nodes outside the neighbourhood of the pivot need to be branched on.
+    fn new(network: &Network, r: Vec<usize>, p: BitSet, x: BitSet) -> Frame {
+        let pivot = p
+            .iter()
+            .chain(x.iter())
+            .max_by_key(|&u| p.intersection(&network.adjacency[u]).len())
+            .unwrap();
+        let candidates = p.difference(&network.adjacency[pivot]).iter().collect_vec();
+
+        Frame {
+            r,
+            p,
+            x,
+            candidates,
+        }
     }
+}
 
-    fn bronkerbosch(&mut self, p: HashSet<N>, r: HashSet<N>, x: HashSet<N>) {
-        let mut p_fp = p.clone();
-        let mut x_fp = x.clone();
+struct MaximalCliques<'a> {
+    network: &'a Network,
+    order: Vec<usize>,
+    next_root: usize,
+    done: BitSet,
+    stack: Vec<Frame>,
+}
 
-        if p.is_empty() {
-            if x.is_empty() {
-                self.max_cliques.push(r.clone());
+impl<'a> Iterator for MaximalCliques<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let network = self.network;
+
+        loop {
+            if let Some(frame) = self.stack.last_mut() {
+                let Some(v) = frame.candidates.pop() else {
+                    self.stack.pop();
+                    continue;
+                };
+
+                let p = frame.p.intersection(&network.adjacency[v]);
+                let x = frame.x.intersection(&network.adjacency[v]);
+                let mut r = frame.r.clone();
+                r.push(v);
+                frame.p.remove(v);
+                frame.x.insert(v);
+
+                if p.is_empty() {
+                    if x.is_empty() {
+                        return Some(network.sorted_names(&r));
+                    }
+                    continue;
+                }
+
+                let frame = Frame::new(network, r, p, x);
+                self.stack.push(frame);
+            } else if self.next_root < self.order.len() {
+                let v = self.order[self.next_root];
+                self.next_root += 1;
+
+                let p = network.adjacency[v].difference(&self.done);
+                let x = network.adjacency[v].intersection(&self.done);
+                self.done.insert(v);
+
+                if p.is_empty() {
+                    if x.is_empty() {
+                        return Some(network.sorted_names(&[v]));
+                    }
+                    continue;
+                }
+
+                let frame = Frame::new(network, vec![v], p, x);
+                self.stack.push(frame);
+            } else {
+                return None;
             }
-            return;
         }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    fn new(size: usize) -> BitSet {
+        BitSet {
+            words: vec![0; size.div_ceil(64)],
+        }
+    }
+
+    fn full(size: usize) -> BitSet {
+        let mut set = BitSet::new(size);
+        for i in 0..size {
+            set.insert(i);
+        }
+        set
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
 
-        for v in p.iter() {
-            let v_neighbours = self.graph.neighbors(*v).collect::<HashSet<N>>();
+    fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
 
-            let p_intersect_v_neighbors = p_fp.intersection(&v_neighbours).cloned().collect();
-            let mut r_union_v = r.clone();
-            r_union_v.insert(*v);
-            let x_intersect_v_neighbors = x_fp.intersection(&v_neighbours).cloned().collect();
+    fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    fn intersection(&self, other: &BitSet) -> BitSet {
+        BitSet {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a & b)
+                .collect(),
+        }
+    }
 
-            self.bronkerbosch(p_intersect_v_neighbors, r_union_v, x_intersect_v_neighbors);
+    fn difference(&self, other: &BitSet) -> BitSet {
+        BitSet {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a & !b)
+                .collect(),
+        }
+    }
 
-            p_fp.remove(v);
-            x_fp.insert(*v);
+    /// Drops every element lower than or equal to `i`.
+    fn retain_above(&mut self, i: usize) {
+        for (w, word) in self.words.iter_mut().enumerate() {
+            let first = w * 64;
+            if first + 64 <= i + 1 {
+                *word = 0;
+            } else if first <= i {
+                *word &= !0u64 << (i + 1 - first);
+            }
         }
     }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            let mut rest = word;
+            std::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
 }
@@ -0,0 +1,575 @@
+use adv_code_2024::*;
+use anyhow::*;
+use code_timing_macros::time_snippet;
+use const_format::concatcp;
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+
+const DAY: &str = "24";
+const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
+
+const TEST: &str = "\
+x00: 1
+x01: 1
+x02: 1
+y00: 0
+y01: 1
+y02: 0
+
+x00 AND y00 -> z00
+x01 XOR y01 -> z01
+x02 OR y02 -> z02
+";
+
+fn main() -> Result<()> {
+    start_day(DAY);
+
+    println!("=== Part 1 ===");
+
+    fn part1(input: &str) -> Result<u64> {
+        let circuit = Circuit::parse(input)?;
+        circuit.output()
+    }
+
+    fn part2(input: &str) -> Result<String> {
+        let circuit = Circuit::parse(input)?;
+        for fault in circuit.faults() {
+            println!("{}", fault);
+        }
+
+        let pairs = circuit.swapped_pairs()?;
+        for (a, b) in pairs.iter() {
+            println!("Swapped: {} <-> {}", a, b);
+        }
+
+        Ok(pairs.iter().flat_map(|(a, b)| [a, b]).sorted().join(","))
+    }
+
+    let result = part1(TEST)?;
+    println!("Test Result 1 = {}", result);
+    assert_eq!(4, result);
+
+    // A correct adder needs no repair, and adds
+    let adder = Circuit::parse(&ripple_carry_adder(24, 12345, 67890, &[]))?;
+    assert_eq!(12345 + 67890, adder.output()?);
+    assert!(adder.faults().is_empty());
+    assert!(adder.swapped_pairs()?.is_empty());
+
+    let swaps = [
+        ("z07", "a07"),
+        ("s10", "a10"),
+        ("z15", "c15"),
+        ("z20", "t20"),
+    ];
+    let result = part2(&ripple_carry_adder(24, 0, 0, &swaps))?;
+    println!("Test Result 2 = {}", result);
+    assert_eq!("a07,a10,c15,s10,t20,z07,z15,z20", result);
+
+    // Numbers are 64 bits wide, and wire names are quoted in the DOT export
+    let error = part1("x00: 1\ny00: 1\n\nx00 AND y00 -> z64\n").unwrap_err();
+    println!("{}", error);
+    assert_eq!(
+        "Wire z64 is past the 64 bits of a number",
+        error.to_string()
+    );
+    let circuit = Circuit::parse("x64: 1\ny00: 1\n\nx64 AND y00 -> z00\n")?;
+    assert!(circuit.with_inputs(1, 1).is_err());
+    let circuit = Circuit::parse("x00: 1\ny00: 1\n\nx00 AND y00 -> a\"b\n")?;
+    assert!(circuit.to_dot().contains("\"gate: 0\" -> \"a\\\"b\";"));
+
+    let mut input_file = File::open(INPUT_FILE)?;
+    let mut buffer = Vec::new();
+    input_file.read_to_end(&mut buffer)?;
+    let input = String::from_utf8_lossy(&buffer);
+
+    let result = time_snippet!(part1(&input)?);
+    println!("Result 1 = {}", result);
+
+    let result = time_snippet!(part2(&input)?);
+    println!("Result 2 = {}", result);
+
+    if let Some(path) = cli_option("dot") {
+        let circuit = Circuit::parse(&input)?;
+        std::fs::write(path, circuit.to_dot())?;
+    }
+
+    Ok(())
+}
+
+/// Puzzle input for a `bits` wide ripple-carry adder of `x` and `y`, with the outputs of the
+/// given gate pairs swapped.
+fn ripple_carry_adder(bits: usize, x: u64, y: u64, swaps: &[(&str, &str)]) -> String {
+    let mut text = String::new();
+    for (name, value) in [("x", x), ("y", y)] {
+        for i in 0..bits {
+            text.push_str(&format!("{}{:02}: {}\n", name, i, (value >> i) & 1));
+        }
+    }
+    text.push('\n');
+
+    let mut gates: Vec<(String, &str, String, String)> = Vec::new();
+    let wire = |name: &str, i: usize| format!("{}{:02}", name, i);
+    let carry = |i: usize| {
+        if i == bits - 1 {
+            wire("z", bits)
+        } else {
+            wire("c", i)
+        }
+    };
+
+    gates.push((wire("x", 0), "XOR", wire("y", 0), wire("z", 0)));
+    gates.push((wire("x", 0), "AND", wire("y", 0), carry(0)));
+    for i in 1..bits {
+        gates.push((wire("x", i), "XOR", wire("y", i), wire("s", i)));
+        gates.push((wire("x", i), "AND", wire("y", i), wire("a", i)));
+        gates.push((wire("s", i), "XOR", carry(i - 1), wire("z", i)));
+        gates.push((wire("s", i), "AND", carry(i - 1), wire("t", i)));
+        gates.push((wire("a", i), "OR", wire("t", i), carry(i)));
+    }
+
+    for (a, op, b, output) in gates {
+        let mut output = output;
+        for (first, second) in swaps {
+            if output == *first {
+                output = second.to_string();
+            } else if output == *second {
+                output = first.to_string();
+            }
+        }
+        text.push_str(&format!("{} {} {} -> {}\n", a, op, b, output));
+    }
+
+    text
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Op {
+    And,
+    Or,
+    Xor,
+}
+
+impl Op {
+    fn apply(&self, a: bool, b: bool) -> bool {
+        match self {
+            Op::And => a & b,
+            Op::Or => a | b,
+            Op::Xor => a ^ b,
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Op::And => write!(f, "AND"),
+            Op::Or => write!(f, "OR"),
+            Op::Xor => write!(f, "XOR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Gate {
+    a: String,
+    op: Op,
+    b: String,
+    output: String,
+}
+
+impl Gate {
+    fn has_input(&self, wire: &str) -> bool {
+        self.a == wire || self.b == wire
+    }
+
+    fn reads_inputs(&self) -> bool {
+        self.a.starts_with(['x', 'y']) && self.b.starts_with(['x', 'y'])
+    }
+
+    fn reads_first_bit(&self) -> bool {
+        self.has_input("x00") && self.has_input("y00")
+    }
+}
+
+/// The ripple-carry adder rule a wire breaks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Rule {
+    // Every z output but the last one is a sum bit
+    OutputNotXor,
+    // The last z output is the carry of the highest bit
+    LastOutputNotOr,
+    // The XOR of a carry and a half sum can only be an output
+    SumNotOutput,
+    // The half sum of two input bits is always added to the carry
+    HalfSumUnused,
+    // The AND gates only ever compute a carry
+    CarryNotOr,
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Rule::OutputNotXor => write!(f, "an output bit must come from a XOR gate"),
+            Rule::LastOutputNotOr => write!(f, "the last output bit must come from an OR gate"),
+            Rule::SumNotOutput => {
+                write!(
+                    f,
+                    "a XOR gate not reading the inputs must drive an output bit"
+                )
+            }
+            Rule::HalfSumUnused => write!(f, "a XOR of two input bits must feed a XOR gate"),
+            Rule::CarryNotOr => write!(f, "an AND gate must feed an OR gate"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fault {
+    wire: String,
+    rule: Rule,
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.wire, self.rule)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Circuit {
+    inputs: BTreeMap<String, bool>,
+    gates: Vec<Gate>,
+}
+
+impl Circuit {
+    fn parse(input: &str) -> Result<Circuit> {
+        let (wires, gates) = input
+            .split_once("\n\n")
+            .ok_or_else(|| anyhow!("Missing the empty line between wires and gates"))?;
+
+        let mut inputs = BTreeMap::new();
+        for line in wires.lines().filter(|l| !l.trim().is_empty()) {
+            let (wire, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid wire '{}'", line))?;
+            let value = match value.trim() {
+                "0" => false,
+                "1" => true,
+                other => bail!("Invalid value '{}' for wire {}", other, wire),
+            };
+            inputs.insert(wire.trim().to_string(), value);
+        }
+
+        let mut parsed = Vec::new();
+        for line in gates.lines().filter(|l| !l.trim().is_empty()) {
+            let parts = line.split_whitespace().collect_vec();
+            let [a, op, b, "->", output] = parts[..] else {
+                bail!("Invalid gate '{}'", line);
+            };
+            let op = match op {
+                "AND" => Op::And,
+                "OR" => Op::Or,
+                "XOR" => Op::Xor,
+                other => bail!("Unknown operation '{}' in gate '{}'", other, line),
+            };
+            parsed.push(Gate {
+                a: a.to_string(),
+                op,
+                b: b.to_string(),
+                output: output.to_string(),
+            });
+        }
+
+        Ok(Circuit {
+            inputs,
+            gates: parsed,
+        })
+    }
+
+    /// Gate indices such that every gate comes after the ones driving its inputs.
+    fn topological_order(&self) -> Result<Vec<usize>> {
+        let mut known: HashSet<&str> = self.inputs.keys().map(|k| k.as_str()).collect();
+        let mut readers: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut missing = vec![0; self.gates.len()];
+        let mut ready = Vec::new();
+
+        for (i, gate) in self.gates.iter().enumerate() {
+            for wire in [&gate.a, &gate.b] {
+                if !known.contains(wire.as_str()) {
+                    missing[i] += 1;
+                    readers.entry(wire).or_default().push(i);
+                }
+            }
+            if missing[i] == 0 {
+                ready.push(i);
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.gates.len());
+        while let Some(i) = ready.pop() {
+            order.push(i);
+            let output = self.gates[i].output.as_str();
+            if !known.insert(output) {
+                continue;
+            }
+            for &reader in readers.get(output).into_iter().flatten() {
+                missing[reader] -= 1;
+                if missing[reader] == 0 {
+                    ready.push(reader);
+                }
+            }
+        }
+
+        if order.len() != self.gates.len() {
+            bail!("The gates form a loop or read wires nothing drives");
+        }
+
+        Ok(order)
+    }
+
+    fn evaluate(&self) -> Result<HashMap<&str, bool>> {
+        let mut values: HashMap<&str, bool> =
+            self.inputs.iter().map(|(k, &v)| (k.as_str(), v)).collect();
+
+        for i in self.topological_order()? {
+            let gate = &self.gates[i];
+            let value = gate
+                .op
+                .apply(values[gate.a.as_str()], values[gate.b.as_str()]);
+            values.insert(&gate.output, value);
+        }
+
+        Ok(values)
+    }
+
+    /// The number formed by the `z` wires, `z00` being the least significant bit.
+    fn output(&self) -> Result<u64> {
+        let values = self.evaluate()?;
+        let number = values
+            .iter()
+            .filter(|(wire, _)| wire.starts_with('z'))
+            .filter(|(_, &value)| value)
+            .map(|(wire, _)| bit_index(wire))
+            .try_fold(0, |number, bit| Ok(number | 1u64 << bit?))?;
+
+        Ok(number)
+    }
+
+    fn input_bits(&self) -> usize {
+        self.inputs.keys().filter(|k| k.starts_with('x')).count()
+    }
+
+    fn with_inputs(&self, x: u64, y: u64) -> Result<Circuit> {
+        let mut circuit = self.clone();
+        for (wire, value) in circuit.inputs.iter_mut() {
+            let number = match wire.chars().next() {
+                Some('x') => x,
+                Some('y') => y,
+                _ => continue,
+            };
+            *value = (number >> bit_index(wire)?) & 1 == 1;
+        }
+        Ok(circuit)
+    }
+
+    fn last_output(&self) -> String {
+        format!("z{:02}", self.input_bits())
+    }
+
+    /// The gate outputs breaking the structure of a ripple-carry adder.
+    fn faults(&self) -> Vec<Fault> {
+        let last_output = self.last_output();
+        let mut faults = Vec::new();
+
+        for gate in self.gates.iter() {
+            let wire = &gate.output;
+            let readers = self
+                .gates
+                .iter()
+                .filter(|g| g.has_input(wire))
+                .collect_vec();
+            let mut fault = |rule| {
+                faults.push(Fault {
+                    wire: wire.clone(),
+                    rule,
+                })
+            };
+
+            if *wire == last_output {
+                if gate.op != Op::Or && self.input_bits() > 1 {
+                    fault(Rule::LastOutputNotOr);
+                }
+                continue;
+            }
+
+            if wire.starts_with('z') && gate.op != Op::Xor {
+                fault(Rule::OutputNotXor);
+            }
+
+            match gate.op {
+                Op::Xor if !gate.reads_inputs() && !wire.starts_with('z') => {
+                    fault(Rule::SumNotOutput)
+                }
+                Op::Xor
+                    if gate.reads_inputs()
+                        && !gate.reads_first_bit()
+                        && !readers.iter().any(|r| r.op == Op::Xor) =>
+                {
+                    fault(Rule::HalfSumUnused)
+                }
+                Op::And if !gate.reads_first_bit() && !readers.iter().any(|r| r.op == Op::Or) => {
+                    fault(Rule::CarryNotOr)
+                }
+                _ => {}
+            }
+        }
+
+        faults
+    }
+
+    fn swap(&mut self, a: &str, b: &str) {
+        for gate in self.gates.iter_mut() {
+            if gate.output == a {
+                gate.output = b.to_string();
+            } else if gate.output == b {
+                gate.output = a.to_string();
+            }
+        }
+    }
+
+    /// True when the circuit passes the rules and adds a set of sample numbers.
+    fn adds(&self) -> bool {
+        if !self.faults().is_empty() {
+            return false;
+        }
+
+        let bits = self.input_bits();
+        let mask = if bits >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << bits) - 1
+        };
+        let mut samples = vec![(mask, mask), (mask, 1)];
+        for bit in 0..bits {
+            samples.push((1 << bit, 0));
+            samples.push((0, 1 << bit));
+            samples.push((1 << bit, 1 << bit));
+        }
+        for i in 1..=16u64 {
+            let x = i.wrapping_mul(0x9E37_79B9_7F4A_7C15) & mask;
+            let y = i.wrapping_mul(0xC2B2_AE3D_27D4_EB4F) & mask;
+            samples.push((x, y));
+        }
+
+        samples.into_iter().all(|(x, y)| {
+            let expected = x as u128 + y as u128;
+            match self.with_inputs(x, y).and_then(|circuit| circuit.output()) {
+                Result::Ok(output) => output as u128 == expected,
+                Err(_) => false,
+            }
+        })
+    }
+
+    /// Pairs the faulty wires so that swapping each pair back gives a working adder.
+    fn swapped_pairs(&self) -> Result<Vec<(String, String)>> {
+        let wires = self
+            .faults()
+            .into_iter()
+            .map(|f| f.wire)
+            .unique()
+            .sorted()
+            .collect_vec();
+
+        // The adder is checked with 64-bit numbers, and its last output is one bit wider
+        if self.input_bits() >= u64::BITS as usize {
+            bail!("A {} bit adder is too wide to check", self.input_bits());
+        }
+        if wires.len() % 2 == 1 {
+            bail!(
+                "{} faulty wires cannot be paired: {}",
+                wires.len(),
+                wires.join(",")
+            );
+        }
+
+        fn pair_up(circuit: &Circuit, wires: &[String]) -> Option<Vec<(String, String)>> {
+            let Some((first, rest)) = wires.split_first() else {
+                return circuit.adds().then(Vec::new);
+            };
+
+            for (i, second) in rest.iter().enumerate() {
+                let mut swapped = circuit.clone();
+                swapped.swap(first, second);
+                let mut others = rest.to_vec();
+                others.remove(i);
+
+                if let Some(mut pairs) = pair_up(&swapped, &others) {
+                    pairs.insert(0, (first.clone(), second.clone()));
+                    return Some(pairs);
+                }
+            }
+
+            None
+        }
+
+        pair_up(self, &wires)
+            .ok_or_else(|| anyhow!("No pairing of {} repairs the adder", wires.join(",")))
+    }
+
+    /// Graphviz DOT with a node per gate and per wire, faulty wires in red.
+    fn to_dot(&self) -> String {
+        let faulty: HashSet<String> = self.faults().into_iter().map(|f| f.wire).collect();
+        let mut dot = String::from("digraph circuit {\n  rankdir=LR;\n");
+
+        // No wire can be named like a gate node: input wires hold no ':' and outputs no space
+        let gate_id = |i: usize| dot_id(&format!("gate: {}", i));
+
+        for wire in self.inputs.keys() {
+            dot.push_str(&format!("  {} [shape=box, color=blue];\n", dot_id(wire)));
+        }
+        for (i, gate) in self.gates.iter().enumerate() {
+            dot.push_str(&format!(
+                "  {} [label=\"{}\", shape=circle];\n",
+                gate_id(i),
+                gate.op
+            ));
+
+            let mut style = if gate.output.starts_with('z') {
+                "shape=box, color=darkgreen".to_string()
+            } else {
+                "shape=plaintext".to_string()
+            };
+            if faulty.contains(&gate.output) {
+                style.push_str(", style=filled, fillcolor=red");
+            }
+            dot.push_str(&format!("  {} [{}];\n", dot_id(&gate.output), style));
+
+            dot.push_str(&format!("  {} -> {};\n", dot_id(&gate.a), gate_id(i)));
+            dot.push_str(&format!("  {} -> {};\n", dot_id(&gate.b), gate_id(i)));
+            dot.push_str(&format!("  {} -> {};\n", gate_id(i), dot_id(&gate.output)));
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+/// The bit a numbered wire such as `z07` stands for, which must fit a 64-bit number.
+fn bit_index(wire: &str) -> Result<u32> {
+    let bit = wire[1..]
+        .parse::<u32>()
+        .with_context(|| format!("Wire {} has no bit number", wire))?;
+    if bit >= u64::BITS {
+        bail!("Wire {} is past the {} bits of a number", wire, u64::BITS);
+    }
+    Ok(bit)
+}
+
+/// A DOT node id, quoted so that any wire name is kept as it is.
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,104 @@
+use adv_code_2024::schematics::Schematics;
+use adv_code_2024::*;
+use anyhow::*;
+use code_timing_macros::time_snippet;
+use const_format::concatcp;
+use std::fs::File;
+use std::io::Read;
+
+const DAY: &str = "25";
+const INPUT_FILE: &str = concatcp!("input/", DAY, ".txt");
+
+const TEST: &str = "\
+#####
+.####
+.####
+.####
+.#.#.
+.#...
+.....
+
+#####
+##.##
+.#.##
+...##
+...#.
+...#.
+.....
+
+.....
+#....
+#....
+#...#
+#.#.#
+#.###
+#####
+
+.....
+.....
+#.#..
+###..
+###.#
+###.#
+#####
+
+.....
+.....
+.....
+#....
+#.#..
+#.#.#
+#####
+";
+
+const TEST2: &str = "\
+###
+.#.
+...
+
+...
+.#.
+###
+
+...
+#..
+###
+";
+
+fn main() -> Result<()> {
+    start_day(DAY);
+
+    println!("=== Part 1 ===");
+
+    fn part1(input: &str) -> Result<u64> {
+        let schematics = Schematics::parse(input)?;
+        Ok(schematics.fitting_pairs())
+    }
+
+    let result = part1(TEST)?;
+    println!("Test Result 1 = {}", result);
+    assert_eq!(3, result);
+
+    let schematics = Schematics::parse(TEST)?;
+    assert_eq!(vec![0, 5, 3, 4, 3], schematics.locks[0].heights);
+    assert_eq!(vec![5, 0, 2, 1, 3], schematics.keys[0].heights);
+
+    // Smaller schematics work the same way
+    assert_eq!(1, part1(TEST2)?);
+
+    let error = Schematics::parse("#####\n.....\n\n##.##\n.....\n").unwrap_err();
+    println!("{}", error);
+    assert!(error.to_string().starts_with("Block 2 at line 4:"));
+    let error = Schematics::parse("#####\n.....\n\n\n\n##.##\n.....\n").unwrap_err();
+    assert!(error.to_string().starts_with("Block 2 at line 6:"));
+
+    let mut input_file = File::open(INPUT_FILE)?;
+    let mut buffer = Vec::new();
+    input_file.read_to_end(&mut buffer)?;
+    let input = String::from_utf8_lossy(&buffer);
+
+    let result = time_snippet!(part1(&input)?);
+    println!("Result 1 = {}", result);
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+// Types shared by the puzzles moving around 2D maps
+
+/// One of the four orthogonal moves on a grid, with rows growing downwards.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
+    /// Offset of a single step, as (row, column).
+    pub fn delta(&self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Right => (0, 1),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+        }
+    }
+
+    /// The arrow used by the puzzles to write this move.
+    pub fn arrow(&self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Right => '>',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+        }
+    }
+
+    pub fn from_arrow(arrow: char) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|d| d.arrow() == arrow)
+    }
+
+    pub fn turn_left(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Right => Direction::Up,
+            Direction::Down => Direction::Right,
+            Direction::Left => Direction::Down,
+        }
+    }
+
+    pub fn turn_right(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        self.turn_left().turn_left()
+    }
+
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, Direction::Left | Direction::Right)
+    }
+
+    /// The (row, column) reached from `position`, unless it would leave the top or left edge.
+    pub fn step(&self, position: (usize, usize)) -> Option<(usize, usize)> {
+        let (dr, dc) = self.delta();
+        Some((
+            position.0.checked_add_signed(dr)?,
+            position.1.checked_add_signed(dc)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directions_round_trip() {
+        for direction in Direction::ALL {
+            assert_eq!(Some(direction), Direction::from_arrow(direction.arrow()));
+            assert_eq!(direction, direction.turn_left().turn_right());
+            assert_eq!(direction, direction.opposite().opposite());
+        }
+        assert_eq!(None, Direction::Up.step((0, 3)));
+        assert_eq!(Some((1, 2)), Direction::Left.step((1, 3)));
+    }
+}
@@ -1,9 +1,41 @@
+pub mod animation;
+pub mod grid;
+pub mod schematics;
+pub mod word_search;
+
 pub fn start_day(day: &str) {
     println!("Advent of Code 2024 - Day {:0>2}", day);
 }
 
 // Additional common functions
 
+/// Returns the value of a command line option given as `--name value` or `--name=value`.
+pub fn cli_option(name: &str) -> Option<String> {
+    find_option(std::env::args().skip(1), name)
+}
+
+/// Returns true when `--name` is present on the command line.
+pub fn cli_flag(name: &str) -> bool {
+    let flag = format!("--{}", name);
+    std::env::args().skip(1).any(|arg| arg == flag)
+}
+
+fn find_option(mut args: impl Iterator<Item = String>, name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let prefix = format!("{}=", flag);
+
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_string());
+        }
+        if arg == flag {
+            return args.next();
+        }
+    }
+
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -12,4 +44,13 @@ mod tests {
     fn it_works() {
         start_day("00");
     }
+
+    #[test]
+    fn finds_options() {
+        let args = || ["--a", "1", "--b=2", "--c"].map(String::from).into_iter();
+        assert_eq!(Some("1".to_string()), find_option(args(), "a"));
+        assert_eq!(Some("2".to_string()), find_option(args(), "b"));
+        assert_eq!(None, find_option(args(), "c"));
+        assert_eq!(None, find_option(args(), "d"));
+    }
 }
@@ -0,0 +1,204 @@
+// Lock and key schematics, read as pin heights and matched with a bitmask per schematic
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Lock,
+    Key,
+}
+
+/// A lock or key, as the height of each column.
+#[derive(Debug, Clone)]
+pub struct Schematic {
+    pub kind: Kind,
+    pub heights: Vec<usize>,
+    /// Rows a lock pin and a key pin share, outside the filled top and bottom rows.
+    pub space: usize,
+    // One bit per pin cell in those rows, column after column
+    mask: Vec<u64>,
+}
+
+impl Schematic {
+    /// Reads the rows of one schematic, top row first.
+    pub fn parse(rows: &[&str]) -> Result<Schematic> {
+        if rows.len() < 2 {
+            bail!("needs at least two rows, found {}", rows.len());
+        }
+
+        let width = rows[0].len();
+        if let Some((r, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != width) {
+            bail!("row {} is {} wide instead of {}", r + 1, row.len(), width);
+        }
+        if let Some(c) = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .find(|&c| c != '#' && c != '.')
+        {
+            bail!("unexpected character '{}'", c);
+        }
+
+        let full = |row: &str| row.chars().all(|c| c == '#');
+        let empty = |row: &str| row.chars().all(|c| c == '.');
+        let last = rows[rows.len() - 1];
+        let kind = if full(rows[0]) && empty(last) {
+            Kind::Lock
+        } else if empty(rows[0]) && full(last) {
+            Kind::Key
+        } else {
+            bail!("is neither a lock (top row filled) nor a key (bottom row filled)");
+        };
+
+        let space = rows.len() - 2;
+        let mut heights = Vec::with_capacity(width);
+        let mut mask = vec![0; (width * space).div_ceil(64)];
+        for c in 0..width {
+            let column: Vec<bool> = rows.iter().map(|row| row.as_bytes()[c] == b'#').collect();
+
+            // Pins grow from the top of a lock and from the bottom of a key
+            let filled = column.iter().filter(|&&f| f).count();
+            let contiguous = match kind {
+                Kind::Lock => column.iter().take(filled).all(|&f| f),
+                Kind::Key => column.iter().rev().take(filled).all(|&f| f),
+            };
+            if !contiguous {
+                bail!("column {} has a gap in its pin", c + 1);
+            }
+
+            let height = filled - 1;
+            let pin = match kind {
+                Kind::Lock => 0..height,
+                Kind::Key => space - height..space,
+            };
+            for r in pin {
+                let bit = c * space + r;
+                mask[bit / 64] |= 1 << (bit % 64);
+            }
+            heights.push(height);
+        }
+
+        Ok(Schematic {
+            kind,
+            heights,
+            space,
+            mask,
+        })
+    }
+
+    /// Width and shared rows, which a lock and a key need in common to be tried together.
+    pub fn size(&self) -> (usize, usize) {
+        (self.heights.len(), self.space)
+    }
+
+    /// True when both have the same size and no column holds a lock pin and a key pin in
+    /// the same row.
+    pub fn fits(&self, other: &Schematic) -> bool {
+        self.size() == other.size() && disjoint(&self.mask, &other.mask)
+    }
+}
+
+fn disjoint(a: &[u64], b: &[u64]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a & b == 0)
+}
+
+#[derive(Debug)]
+pub struct Schematics {
+    pub locks: Vec<Schematic>,
+    pub keys: Vec<Schematic>,
+}
+
+impl Schematics {
+    /// Reads schematics separated by blank lines. Errors name the block, counting the
+    /// schematics only, and the line it starts on.
+    pub fn parse(input: &str) -> Result<Schematics> {
+        let mut locks = Vec::new();
+        let mut keys = Vec::new();
+
+        for (i, (line, rows)) in blocks(input).into_iter().enumerate() {
+            let schematic = Schematic::parse(&rows)
+                .map_err(|e| anyhow!("Block {} at line {}: {}", i + 1, line, e))?;
+            match schematic.kind {
+                Kind::Lock => locks.push(schematic),
+                Kind::Key => keys.push(schematic),
+            }
+        }
+
+        Ok(Schematics { locks, keys })
+    }
+
+    /// Lock and key pairs with no overlapping pin. Keys are bucketed by size so each lock
+    /// only tries the keys it could fit.
+    pub fn fitting_pairs(&self) -> u64 {
+        let mut buckets: HashMap<(usize, usize), Vec<&[u64]>> = HashMap::new();
+        for key in &self.keys {
+            buckets.entry(key.size()).or_default().push(&key.mask);
+        }
+
+        self.locks
+            .iter()
+            .filter_map(|lock| Some((lock, buckets.get(&lock.size())?)))
+            .map(|(lock, keys)| keys.iter().filter(|key| disjoint(&lock.mask, key)).count() as u64)
+            .sum()
+    }
+}
+
+/// Groups the lines between blank ones, with the line number each group starts on.
+fn blocks(input: &str) -> Vec<(usize, Vec<&str>)> {
+    let mut blocks: Vec<(usize, Vec<&str>)> = Vec::new();
+    let mut in_block = false;
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            in_block = false;
+        } else if in_block {
+            blocks.last_mut().unwrap().1.push(line);
+        } else {
+            blocks.push((number + 1, vec![line]));
+            in_block = true;
+        }
+    }
+
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_blocks_not_blank_lines() {
+        let error = Schematics::parse("#####\n.....\n\n\n\n##.##\n.....\n").unwrap_err();
+        assert_eq!(
+            "Block 2 at line 6: is neither a lock (top row filled) nor a key (bottom row filled)",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn matches_wide_schematics() {
+        // 16 columns, too many height profiles to enumerate one by one
+        let lock = ["################", "#.#.#.#.#.#.#.#.", "................"];
+        let key = ["................", ".#.#.#.#.#.#.#.#", "################"];
+        let input = format!("{}\n\n{}\n", lock.join("\n"), key.join("\n"));
+        assert_eq!(1, Schematics::parse(&input).unwrap().fitting_pairs());
+
+        // Masks spanning several words, and a key of another size that is never tried
+        let row =
+            |pin: usize| -> String { (0..70).map(|c| if c == pin { '#' } else { '.' }).collect() };
+        let (full, empty): (String, String) = ("#".repeat(70), ".".repeat(70));
+        let input = [
+            [full.clone(), row(69), empty.clone()].join("\n"),
+            [empty.clone(), row(69), full.clone()].join("\n"),
+            [empty.clone(), row(68), full.clone()].join("\n"),
+            [empty.clone(), empty.clone(), empty.clone(), full.clone()].join("\n"),
+        ]
+        .join("\n\n");
+        let schematics = Schematics::parse(&input).unwrap();
+        assert!(!schematics.locks[0].fits(&schematics.keys[0]));
+        assert!(schematics.locks[0].fits(&schematics.keys[1]));
+        assert!(!schematics.locks[0].fits(&schematics.keys[2]));
+        assert_eq!(1, schematics.fitting_pairs());
+    }
+}
@@ -0,0 +1,386 @@
+// Word search over letter grids, for words read along lines and for 2D templates
+
+use aho_corasick::AhoCorasick;
+use anyhow::{bail, Result};
+use colored::{Color, Colorize};
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+/// One of the eight ways to read a grid, with rows growing downwards.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Orientation {
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+    North,
+    NorthEast,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 8] = [
+        Orientation::East,
+        Orientation::SouthEast,
+        Orientation::South,
+        Orientation::SouthWest,
+        Orientation::West,
+        Orientation::NorthWest,
+        Orientation::North,
+        Orientation::NorthEast,
+    ];
+
+    /// Offset of a single step, as (row, column).
+    pub fn delta(&self) -> (isize, isize) {
+        match self {
+            Orientation::East => (0, 1),
+            Orientation::SouthEast => (1, 1),
+            Orientation::South => (1, 0),
+            Orientation::SouthWest => (1, -1),
+            Orientation::West => (0, -1),
+            Orientation::NorthWest => (-1, -1),
+            Orientation::North => (-1, 0),
+            Orientation::NorthEast => (-1, 1),
+        }
+    }
+
+    pub fn opposite(&self) -> Orientation {
+        Orientation::ALL[(*self as usize + 4) % 8]
+    }
+}
+
+/// Where a word or a template was found. Words give the cell of their first letter and the
+/// way they read. Templates give the top left corner of the placed template and the way its
+/// rows read: `East` as written, `South` after a quarter turn clockwise, and so on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Index of the word, or of the template, in the list searched for.
+    pub pattern: usize,
+    pub start: (usize, usize),
+    pub orientation: Orientation,
+    /// The matched cells, in reading order for words.
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// A rectangle of letters and wildcards, matched wherever all of its letters are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    rows: Vec<Vec<Option<u8>>>,
+}
+
+impl Template {
+    /// Reads a template written as text, using `wildcard` for cells matching anything.
+    pub fn parse(text: &str, wildcard: char) -> Result<Template> {
+        let rows = text
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                l.trim()
+                    .chars()
+                    .map(|c| if c == wildcard { None } else { Some(c as u8) })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        if rows.is_empty() {
+            bail!("The template is empty");
+        }
+        if rows.iter().any(|row| row.len() != rows[0].len()) {
+            bail!("The template rows do not all have the same width");
+        }
+
+        Ok(Template { rows })
+    }
+
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    /// The template turned a quarter clockwise.
+    pub fn rotate(&self) -> Template {
+        let rows = (0..self.width())
+            .map(|c| (0..self.height()).rev().map(|r| self.rows[r][c]).collect())
+            .collect();
+        Template { rows }
+    }
+
+    /// Every distinct quarter turn of the template, as written first, with the way its rows
+    /// read once turned. Symmetric templates give fewer than four.
+    pub fn rotations(&self) -> Vec<(Orientation, Template)> {
+        let orientations = [
+            Orientation::East,
+            Orientation::South,
+            Orientation::West,
+            Orientation::North,
+        ];
+
+        let mut rotations: Vec<(Orientation, Template)> = Vec::new();
+        let mut template = self.clone();
+        for orientation in orientations {
+            if rotations.iter().all(|(_, t)| *t != template) {
+                rotations.push((orientation, template.clone()));
+            }
+            template = template.rotate();
+        }
+        rotations
+    }
+}
+
+/// A rectangular grid of letters to search.
+#[derive(Debug, Clone)]
+pub struct WordSearch {
+    grid: Vec<Vec<u8>>,
+}
+
+impl WordSearch {
+    pub fn new(input: &str) -> Result<WordSearch> {
+        let grid = input
+            .lines()
+            .map(|l| l.trim_end())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.as_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        if let Some((r, row)) = grid
+            .iter()
+            .enumerate()
+            .find(|(_, row)| row.len() != grid[0].len())
+        {
+            bail!(
+                "Row {} is {} letters wide instead of {}",
+                r + 1,
+                row.len(),
+                grid[0].len()
+            );
+        }
+
+        Ok(WordSearch { grid })
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.grid.first().map_or(0, |row| row.len())
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<u8> {
+        self.grid.get(row).and_then(|r| r.get(column)).copied()
+    }
+
+    /// Cells of every full line read East, South, South East and South West.
+    fn lines(&self) -> Vec<(Orientation, Vec<(usize, usize)>)> {
+        let (height, width) = (self.height(), self.width());
+        let mut starts = Vec::new();
+        starts.extend((0..height).map(|r| (Orientation::East, (r, 0))));
+        starts.extend((0..width).map(|c| (Orientation::South, (0, c))));
+        starts.extend((0..width).map(|c| (Orientation::SouthEast, (0, c))));
+        starts.extend((1..height).map(|r| (Orientation::SouthEast, (r, 0))));
+        starts.extend((0..width).map(|c| (Orientation::SouthWest, (0, c))));
+        starts.extend((1..height).map(|r| (Orientation::SouthWest, (r, width - 1))));
+
+        starts
+            .into_iter()
+            .map(|(orientation, start)| {
+                let (dr, dc) = orientation.delta();
+                let mut cells = Vec::new();
+                let mut cell = Some(start);
+                while let Some((r, c)) = cell.filter(|&(r, c)| r < height && c < width) {
+                    cells.push((r, c));
+                    cell = r.checked_add_signed(dr).zip(c.checked_add_signed(dc));
+                }
+                (orientation, cells)
+            })
+            .collect()
+    }
+
+    /// Every occurrence of the words, read in any of the eight orientations. The words and
+    /// their reversals go in one Aho–Corasick automaton, so each line is scanned only once.
+    pub fn find_words(&self, words: &[&str]) -> Result<Vec<Match>> {
+        if words.is_empty() || words.iter().any(|w| w.is_empty()) {
+            bail!("Needs at least one word, and no empty ones");
+        }
+
+        let reversed = words
+            .iter()
+            .map(|w| w.bytes().rev().collect::<Vec<u8>>())
+            .collect::<Vec<_>>();
+        let patterns = words
+            .iter()
+            .map(|w| w.as_bytes().to_vec())
+            .chain(reversed.iter().cloned())
+            .collect::<Vec<_>>();
+        let automaton = AhoCorasick::new(&patterns)?;
+
+        let mut matches = Vec::new();
+        for (orientation, cells) in self.lines() {
+            let letters = cells
+                .iter()
+                .map(|&(r, c)| self.grid[r][c])
+                .collect::<Vec<u8>>();
+            for found in automaton.find_overlapping_iter(&letters) {
+                let pattern = found.pattern().as_usize();
+                let word = pattern % words.len();
+                let mut found_cells = cells[found.start()..found.end()].to_vec();
+
+                // Palindromes and single letters would otherwise be found more than once
+                let backwards = pattern >= words.len();
+                if backwards && reversed[word] == words[word].as_bytes() {
+                    continue;
+                }
+                if found_cells.len() == 1 && orientation != Orientation::East {
+                    continue;
+                }
+
+                let orientation = if backwards {
+                    found_cells.reverse();
+                    orientation.opposite()
+                } else {
+                    orientation
+                };
+                matches.push(Match {
+                    pattern: word,
+                    start: found_cells[0],
+                    orientation,
+                    cells: found_cells,
+                });
+            }
+        }
+
+        matches.sort_by_key(|m| (m.start, m.orientation, m.pattern));
+        Ok(matches)
+    }
+
+    /// Every placement of the templates, in any of their distinct quarter turns.
+    pub fn find_templates(&self, templates: &[Template]) -> Vec<Match> {
+        let mut matches = Vec::new();
+        for (pattern, template) in templates.iter().enumerate() {
+            for (orientation, template) in template.rotations() {
+                if template.height() > self.height() || template.width() > self.width() {
+                    continue;
+                }
+                for r in 0..=self.height() - template.height() {
+                    for c in 0..=self.width() - template.width() {
+                        if let Some(cells) = self.place(&template, (r, c)) {
+                            matches.push(Match {
+                                pattern,
+                                start: (r, c),
+                                orientation,
+                                cells,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        matches.sort_by_key(|m| (m.start, m.orientation, m.pattern));
+        matches
+    }
+
+    /// The cells matched by the template's letters with its top left corner at `start`.
+    fn place(&self, template: &Template, start: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let mut cells = Vec::new();
+        for (dr, row) in template.rows.iter().enumerate() {
+            for (dc, letter) in row.iter().enumerate() {
+                let (r, c) = (start.0 + dr, start.1 + dc);
+                match letter {
+                    Some(letter) if self.grid[r][c] != *letter => return None,
+                    Some(_) => cells.push((r, c)),
+                    None => {}
+                }
+            }
+        }
+        Some(cells)
+    }
+}
+
+/// Colours told apart on a terminal, given to the matches in turn.
+const COLORS: [(Color, [u8; 3]); 6] = [
+    (Color::Red, [220, 50, 47]),
+    (Color::Green, [133, 153, 0]),
+    (Color::Yellow, [181, 137, 0]),
+    (Color::Blue, [38, 139, 210]),
+    (Color::Magenta, [211, 54, 130]),
+    (Color::Cyan, [42, 161, 152]),
+];
+
+impl WordSearch {
+    /// Index of the last match covering each cell.
+    fn owners(&self, matches: &[Match]) -> Vec<Vec<Option<usize>>> {
+        let mut owners = vec![vec![None; self.width()]; self.height()];
+        for (i, m) in matches.iter().enumerate() {
+            for &(r, c) in m.cells.iter() {
+                owners[r][c] = Some(i);
+            }
+        }
+        owners
+    }
+
+    /// The grid with every letter outside the matches replaced by `.`, as the puzzle shows
+    /// it. With `colors`, each match gets its own colour on the terminal.
+    pub fn highlight(&self, matches: &[Match], colors: bool) -> String {
+        let mut text = String::new();
+        for (row, owners) in self.grid.iter().zip(self.owners(matches)) {
+            for (&letter, owner) in row.iter().zip(owners) {
+                let letter = (letter as char).to_string();
+                match owner {
+                    Some(i) if colors => {
+                        text += &letter.color(COLORS[i % COLORS.len()].0).bold().to_string()
+                    }
+                    Some(_) => text += &letter,
+                    None => text.push('.'),
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Saves the highlighted grid as a picture, one `scale` pixels square per cell, in the
+    /// colour of its match or dark grey.
+    pub fn save_image(&self, matches: &[Match], scale: u32, path: impl AsRef<Path>) -> Result<()> {
+        let scale = scale.max(1);
+        let owners = self.owners(matches);
+        let image = RgbImage::from_fn(
+            self.width() as u32 * scale,
+            self.height() as u32 * scale,
+            |x, y| match owners[(y / scale) as usize][(x / scale) as usize] {
+                Some(i) => Rgb(COLORS[i % COLORS.len()].1),
+                None => Rgb([40, 40, 40]),
+            },
+        );
+        image.save(path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_words_once() {
+        let search = WordSearch::new("ABA\nXBX\nABA\n").unwrap();
+        let matches = search.find_words(&["ABA", "B"]).unwrap();
+        // The palindrome on the two rows and the two diagonals, and each B once
+        assert_eq!(4, matches.iter().filter(|m| m.pattern == 0).count());
+        assert_eq!(3, matches.iter().filter(|m| m.pattern == 1).count());
+        assert!(WordSearch::new("AB\nA\n").is_err());
+    }
+
+    #[test]
+    fn rotates_templates() {
+        let cross = Template::parse("M.S\n.A.\nM.S", '.').unwrap();
+        assert_eq!(4, cross.rotations().len());
+        let plus = Template::parse(".A.\nAAA\n.A.", '.').unwrap();
+        assert_eq!(1, plus.rotations().len());
+        assert_eq!(cross, cross.rotate().rotate().rotate().rotate());
+    }
+}